@@ -0,0 +1,200 @@
+//! Waybar / eww custom-module output for `niri-switch bar`.
+//!
+//! Each daemon state snapshot is rendered into one JSON line with the fields
+//! waybar's `custom` module understands (`text`, `tooltip`, `class`, `alt`).
+//! `text` comes from a user format string with `{placeholder}` fields.
+//! waybar renders both `text` and `tooltip` as Pango markup, so window titles
+//! and workspace names are escaped; the format string itself is not, and may
+//! carry markup of its own.
+
+use serde::Serialize;
+
+use crate::ipc::NiriState;
+
+pub const DEFAULT_FORMAT: &str = "{workspaces}  {title}";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BarOutput {
+    pub text: String,
+    pub tooltip: String,
+    pub class: Vec<String>,
+    pub alt: String,
+}
+
+pub fn render(state: &NiriState, format: &str) -> BarOutput {
+    // The overlay's order: outputs left to right, then by index.
+    let workspaces = state.workspaces_in_order();
+
+    let windows_on = |ws_id: u64| state.windows_by_workspace.get(&ws_id).map_or(0, Vec::len);
+    let urgent_on = |ws_id: u64| {
+        state.windows_by_workspace.get(&ws_id)
+        .map_or(0, |wins| wins.iter().filter(|w| w.is_urgent).count())
+    };
+
+    let focused_ws = workspaces.iter().find(|ws| Some(ws.id) == state.focused_workspace_id);
+    let focused_win = state.windows_by_workspace.values().flatten()
+    .find(|w| Some(w.id) == state.focused_window_id);
+    let urgent: usize = state.windows_by_workspace.values().flatten().filter(|w| w.is_urgent).count();
+
+    let ws_label = |ws: &niri_ipc::Workspace| ws.name.clone().unwrap_or_else(|| ws.idx.to_string());
+    let workspace = focused_ws.map(|ws| ws_label(ws)).unwrap_or_default();
+
+    let text = expand(format, |key| Some(escape_markup(&match key {
+        "workspaces"    => workspaces.iter().map(|ws| {
+            let mut item = format!("{}:{}", ws_label(ws), windows_on(ws.id));
+            if ws.is_urgent || urgent_on(ws.id) > 0 { item.push('!'); }
+            if ws.is_focused { format!("[{item}]") } else { item }
+        }).collect::<Vec<_>>().join(" "),
+        "workspace"     => workspace.clone(),
+        "workspace_idx" => focused_ws.map(|ws| ws.idx.to_string()).unwrap_or_default(),
+        "ws_windows"    => focused_ws.map_or(0, |ws| windows_on(ws.id)).to_string(),
+        "windows"       => state.total_windows().to_string(),
+        "urgent"        => urgent.to_string(),
+        "title"         => focused_win.and_then(|w| w.title.clone()).unwrap_or_default(),
+        "app_id"        => focused_win.and_then(|w| w.app_id.clone()).unwrap_or_default(),
+        _               => return None,
+    })));
+
+    let mut tooltip: Vec<String> = workspaces.iter().map(|ws| {
+        let count = windows_on(ws.id);
+        let marker = if ws.is_focused { "▸" } else { " " };
+        let output = ws.output.as_deref().map(|o| format!(" on {o}")).unwrap_or_default();
+        format!("{marker} ws {}{output} — {count} window{}", ws_label(ws), if count == 1 { "" } else { "s" })
+    }).collect();
+    if let Some(win) = focused_win {
+        tooltip.push(format!(
+            "{} — {}",
+            win.app_id.as_deref().unwrap_or("?"),
+            win.title.as_deref().unwrap_or(""),
+        ));
+    }
+
+    let mut class = Vec::new();
+    if urgent > 0 || workspaces.iter().any(|ws| ws.is_urgent) {
+        class.push("urgent".to_string());
+    }
+    if focused_ws.is_some_and(|ws| windows_on(ws.id) == 0) {
+        class.push("empty".to_string());
+    }
+
+    BarOutput { text, tooltip: escape_markup(&tooltip.join("\n")), class, alt: workspace }
+}
+
+/// Escapes the characters Pango markup treats specially.
+fn escape_markup(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            c   => out.push(c),
+        }
+    }
+    out
+}
+
+/// Replaces every `{key}` in `format` for which `lookup` has a value.
+/// Unknown keys and stray braces are left as they are.
+fn expand(format: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(format.len());
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').and_then(|end| lookup(&after[..end]).map(|v| (end, v))) {
+            Some((end, value)) => {
+                out.push_str(&value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::mock::{window, workspace};
+    use crate::ipc::reducer::reduce;
+    use crate::ipc::NiriEvent;
+
+    fn state() -> NiriState {
+        let mut state = NiriState::default();
+        let mut web = workspace(2, 2);
+        web.name = Some("<web>".into());
+        let mut focused = window(10, 1);
+        focused.is_focused = true;
+        focused.title = Some("Tom & Jerry <b>".into());
+        let mut urgent = window(11, 2);
+        urgent.is_urgent = true;
+        reduce(&mut state, &NiriEvent::WorkspacesChanged { workspaces: vec![workspace(1, 1), web] });
+        reduce(&mut state, &NiriEvent::WindowsChanged { windows: vec![focused, urgent] });
+        state
+    }
+
+    #[test]
+    fn placeholders_are_filled_and_escaped() {
+        let state = state();
+        let cases = [
+            ("{workspaces}  {title}",                "[1:1] &lt;web&gt;:1!  Tom &amp; Jerry &lt;b&gt;"),
+            ("{workspace}/{workspace_idx}",          "1/1"),
+            ("{ws_windows} of {windows}, {urgent}!", "1 of 2, 1!"),
+            ("<b>{app_id}</b>",                      "<b>test.app</b>"),
+        ];
+        for (format, text) in cases {
+            assert_eq!(render(&state, format).text, text, "{format}");
+        }
+
+        let out = render(&state, DEFAULT_FORMAT);
+        assert_eq!(out.tooltip, "▸ ws 1 on MOCK-1 — 1 window\n  ws &lt;web&gt; on MOCK-1 — 1 window\ntest.app — Tom &amp; Jerry &lt;b&gt;");
+        assert_eq!(out.class, vec!["urgent".to_string()]);
+        assert_eq!(out.alt, "1");
+    }
+
+    #[test]
+    fn workspaces_follow_the_monitor_layout() {
+        use crate::ipc::mock::output;
+        use crate::ipc::reducer::set_outputs;
+
+        let mut state = NiriState::default();
+        let on = |id, idx, output: &str| {
+            let mut ws = workspace(id, idx);
+            ws.name = Some(format!("{output}/{idx}"));
+            ws.output = Some(output.into());
+            ws.is_focused = id == 4;
+            ws
+        };
+        let workspaces = vec![on(1, 2, "eDP-1"), on(2, 1, "DP-2"), on(3, 1, "HDMI-A-1"), on(4, 1, "eDP-1")];
+        reduce(&mut state, &NiriEvent::WorkspacesChanged { workspaces });
+        // Left to right: the laptop panel, then HDMI, then DP.
+        let outputs = [output("DP-2", 3840), output("eDP-1", 0), output("HDMI-A-1", 1920)];
+        set_outputs(&mut state, &outputs.into_iter().map(|o| (o.name.clone(), o)).collect());
+
+        let out = render(&state, "{workspaces}");
+        assert_eq!(out.text, "[eDP-1/1:0] eDP-1/2:0 HDMI-A-1/1:0 DP-2/1:0");
+        assert!(out.tooltip.starts_with("▸ ws eDP-1/1 on eDP-1"), "{}", out.tooltip);
+    }
+
+    #[test]
+    fn expand_leaves_unknown_keys_and_stray_braces() {
+        let lookup = |key: &str| (key == "a").then(|| "A".to_string());
+        let cases = [
+            ("{a}{a}",     "AA"),
+            ("{b} {a}",    "{b} A"),
+            ("{ {a} }",    "{ A }"),
+            ("{a",         "{a"),
+            ("a}",         "a}"),
+            ("{{a}}",      "{A}"),
+            ("",           ""),
+        ];
+        for (format, expected) in cases {
+            assert_eq!(expand(format, lookup), expected, "{format}");
+        }
+    }
+}
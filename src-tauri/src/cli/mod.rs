@@ -0,0 +1,558 @@
+//! Command-line front end: turns `niri-switch <subcommand>` into a request to
+//! the running daemon and reports the reply on stdout / via the exit code.
+
+pub mod bar;
+pub mod select;
+
+use std::io::Write;
+
+use crate::control::{self, DaemonRequest, DaemonResponse};
+use crate::error::CommandError;
+use crate::ipc::types::NIRI_IPC_VERSION;
+use crate::ipc::{Compatibility, ConnectionStatus, NiriState};
+use crate::DaemonOptions;
+
+pub const USAGE: &str = "\
+usage: niri-switch [command]
+
+With no command, starts the overlay daemon.
+
+commands:
+  daemon [options]         start the overlay daemon (the default)
+      --replace            ask an already running daemon to quit and take over
+      --record <file>      write every raw niri event to <file> with timestamps
+      --replay <file>      replay a recording instead of connecting to niri
+  show                     show the overlay
+  hide                     hide the overlay
+  toggle                   show or hide the overlay (alias: --toggle)
+  state                    print workspaces and windows tracked by the daemon
+  state --json [path]      print the daemon's state as JSON, optionally only the
+                           jq-style path (e.g. .focused_window_id, .workspaces[].name)
+      -c, --compact        one line per value instead of pretty-printed
+      -r, --raw            print selected strings without quotes
+  subscribe                print the state as a JSON line now and on every change
+  subscribe --events       print raw niri events as JSON lines
+  bar                      print waybar/eww custom-module JSON on every change
+      --format <fmt>       text format (default: \"{workspaces}  {title}\")
+      --once               print a single line and exit
+                           format fields: {workspaces} {workspace} {workspace_idx}
+                           {ws_windows} {windows} {urgent} {title} {app_id}
+  status                   print daemon status
+  focus <window-id>        focus a window
+  focus-previous           focus the previously focused window
+  cycle-next, cycle-prev   move the alt-tab selection (shows the overlay)
+  commit                   focus the selected window and hide the overlay
+  cancel                   end cycling without changing focus
+  move <window-id> <ws>    move a window to workspace <ws>
+  workspace <ws>           focus workspace <ws>
+                           <ws> is id:<id>, idx:<index>, name:<name>, or a bare
+                           index/name; indices count on the focused output
+  move-to-monitor <window-id> <output>
+                           move a window to the monitor named <output>
+  move-workspace <ws> <output>
+                           move a workspace and its windows to <output>
+  close <window-id>        close a window
+  quit                     stop the running daemon
+  help                     print this message
+
+exit codes:
+  0  success
+  1  the daemon or niri rejected the request
+  2  invalid usage
+  3  the daemon is not running
+  4  niri is not running or not answering
+  5  the window no longer exists";
+
+// ── Exit codes ────────────────────────────────────────────────────────────────
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_REJECTED: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NO_DAEMON: i32 = 3;
+pub const EXIT_NO_NIRI: i32 = 4;
+pub const EXIT_GONE: i32 = 5;
+
+/// Exit code for a request the daemon answered with an error.
+pub fn exit_code(err: &CommandError) -> i32 {
+    match err {
+        CommandError::NiriUnavailable { .. } | CommandError::Timeout { .. } => EXIT_NO_NIRI,
+        CommandError::WindowGone { .. } => EXIT_GONE,
+        _ => EXIT_REJECTED,
+    }
+}
+
+// ── Parsing ───────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Start the overlay daemon in this process.
+    Daemon(DaemonOptions),
+    Help,
+    /// Send a request to the running daemon.
+    Send(DaemonRequest),
+    /// Fetch the daemon's state and print it.
+    State(StateOptions),
+    /// Print the daemon's state (or raw events) as JSON lines until it exits.
+    Subscribe { events: bool },
+    /// Print waybar-compatible JSON lines describing the state.
+    Bar { format: String, once: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StateOptions {
+    pub json: bool,
+    pub compact: bool,
+    pub raw: bool,
+    pub path: Option<select::Path>,
+}
+
+/// Parses the arguments after the program name.
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut args = args.iter().map(String::as_str);
+    let Some(sub) = args.next() else { return Ok(Command::Daemon(DaemonOptions::default())) };
+
+    let req = match sub {
+        "daemon"                 => return parse_daemon(args),
+        "--replace" | "--record" | "--replay" => {
+            return parse_daemon(std::iter::once(sub).chain(args));
+        }
+        "help" | "--help" | "-h" => return Ok(Command::Help),
+        "show"                   => DaemonRequest::Show,
+        "hide"                   => DaemonRequest::Hide,
+        "toggle" | "--toggle"    => DaemonRequest::Toggle,
+        "state"                  => return parse_state(args).map(Command::State),
+        "bar"                    => return parse_bar(args),
+        "status"                 => DaemonRequest::Status,
+        "focus"                  => DaemonRequest::FocusWindow { id: arg(args.next(), "window id")? },
+        "focus-previous"         => DaemonRequest::FocusPrevious,
+        "cycle-next"             => DaemonRequest::CycleNext,
+        "cycle-prev"             => DaemonRequest::CyclePrev,
+        "commit"                 => DaemonRequest::Commit,
+        "cancel"                 => DaemonRequest::Cancel,
+        "move"                   => DaemonRequest::MoveWindowToWorkspace {
+            window_id: arg(args.next(), "window id")?,
+            workspace: control::protocol::parse_workspace(args.next().ok_or("missing workspace")?)?,
+        },
+        "workspace"              => DaemonRequest::FocusWorkspace {
+            workspace: control::protocol::parse_workspace(args.next().ok_or("missing workspace")?)?,
+        },
+        "move-to-monitor"        => DaemonRequest::MoveWindowToMonitor {
+            window_id: arg(args.next(), "window id")?,
+            output: args.next().ok_or("missing output")?.into(),
+        },
+        "move-workspace"         => DaemonRequest::MoveWorkspaceToMonitor {
+            workspace: control::protocol::parse_workspace(args.next().ok_or("missing workspace")?)?,
+            output: args.next().ok_or("missing output")?.into(),
+        },
+        "close"                  => DaemonRequest::CloseWindow { id: arg(args.next(), "window id")? },
+        "quit"                   => DaemonRequest::Quit,
+        "subscribe"              => match args.next() {
+            None             => return Ok(Command::Subscribe { events: false }),
+            Some("--events") => return Ok(Command::Subscribe { events: true }),
+            Some(other)      => return Err(format!("unexpected argument `{other}` for `subscribe`")),
+        },
+        other                    => return Err(format!("unknown command `{other}`")),
+    };
+    if let Some(extra) = args.next() {
+        return Err(format!("unexpected argument `{extra}` for `{sub}`"));
+    }
+    Ok(Command::Send(req))
+}
+
+fn parse_daemon<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<Command, String> {
+    let mut opts = DaemonOptions::default();
+    while let Some(arg) = args.next() {
+        match arg {
+            "--replace" => opts.replace = true,
+            "--record"  => opts.record = Some(args.next().ok_or("missing file for --record")?.into()),
+            "--replay"  => opts.replay = Some(args.next().ok_or("missing file for --replay")?.into()),
+            other       => return Err(format!("unexpected argument `{other}` for `daemon`")),
+        }
+    }
+    Ok(Command::Daemon(opts))
+}
+
+fn parse_state<'a>(args: impl Iterator<Item = &'a str>) -> Result<StateOptions, String> {
+    let mut opts = StateOptions::default();
+    for arg in args {
+        match arg {
+            "--json"            => opts.json = true,
+            "-c" | "--compact"  => opts.compact = true,
+            "-r" | "--raw"      => opts.raw = true,
+            path if path.starts_with('.') && opts.path.is_none() => {
+                opts.path = Some(path.parse()?);
+            }
+            other               => return Err(format!("unexpected argument `{other}` for `state`")),
+        }
+    }
+    if opts.path.is_some() || opts.compact || opts.raw {
+        // A path or output flag only makes sense for JSON output.
+        opts.json = true;
+    }
+    Ok(opts)
+}
+
+fn parse_bar<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<Command, String> {
+    let mut format = bar::DEFAULT_FORMAT.to_string();
+    let mut once = false;
+    while let Some(arg) = args.next() {
+        match arg {
+            "--format" => format = args.next().ok_or("missing value for --format")?.to_string(),
+            "--once"   => once = true,
+            other      => return Err(format!("unexpected argument `{other}` for `bar`")),
+        }
+    }
+    Ok(Command::Bar { format, once })
+}
+
+fn arg<T: std::str::FromStr>(arg: Option<&str>, what: &str) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("missing {what}"))?;
+    arg.parse().map_err(|_| format!("invalid {what} `{arg}`"))
+}
+
+// ── Execution ─────────────────────────────────────────────────────────────────
+
+/// Runs a client-side command, prints the outcome and returns the exit code.
+pub fn execute(cmd: &Command) -> i32 {
+    match cmd {
+        Command::Daemon(_) | Command::Help => EXIT_OK,
+        Command::Send(req) => match request(req) {
+            Ok(resp) => {
+                print_response(resp);
+                EXIT_OK
+            }
+            Err(code) => code,
+        },
+        Command::State(opts) => match request(&DaemonRequest::State) {
+            Ok(DaemonResponse::State(state)) if opts.json => print_state_json(&state, opts),
+            Ok(DaemonResponse::State(state)) => {
+                print_state(&state);
+                EXIT_OK
+            }
+            Ok(other) => {
+                eprintln!("niri-switch: unexpected reply {other:?}");
+                EXIT_REJECTED
+            }
+            Err(code) => code,
+        },
+        Command::Subscribe { events } => {
+            let req = if *events { DaemonRequest::SubscribeEvents } else { DaemonRequest::Subscribe };
+            subscribe(&req, |line| writeln!(std::io::stdout(), "{line}"))
+        }
+        Command::Bar { format, once: true } => match request(&DaemonRequest::State) {
+            Ok(DaemonResponse::State(state)) => {
+                println!("{}", serde_json::to_string(&bar::render(&state, format)).unwrap_or_default());
+                EXIT_OK
+            }
+            Ok(other) => {
+                eprintln!("niri-switch: unexpected reply {other:?}");
+                EXIT_REJECTED
+            }
+            Err(code) => code,
+        },
+        Command::Bar { format, once: false } => {
+            let mut last = String::new();
+            subscribe(&DaemonRequest::Subscribe, |line| {
+                let state: NiriState = serde_json::from_str(line)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                let out = serde_json::to_string(&bar::render(&state, format))?;
+                // Most state changes (e.g. a window moving) don't change the bar.
+                if out != last {
+                    writeln!(std::io::stdout(), "{out}")?;
+                    last = out;
+                }
+                Ok(())
+            })
+        }
+    }
+}
+
+/// Streams the lines of a subscription into `on_line` until the daemon exits.
+fn subscribe(req: &DaemonRequest, mut on_line: impl FnMut(&str) -> std::io::Result<()>) -> i32 {
+    let lines = match control::client::subscribe(req) {
+        Ok(Ok(lines)) => lines,
+        Ok(Err(e)) => {
+            eprintln!("niri-switch: {e}");
+            return exit_code(&e);
+        }
+        Err(e) => {
+            eprintln!("niri-switch: {e}");
+            return EXIT_NO_DAEMON;
+        }
+    };
+    for line in lines {
+        let Ok(line) = line else { break };
+        // Widgets read line by line from a pipe: flush every line, and stop
+        // quietly once the reading end has gone away.
+        if on_line(&line).and_then(|()| std::io::stdout().flush()).is_err() {
+            return EXIT_OK;
+        }
+    }
+    eprintln!("niri-switch: daemon closed the subscription");
+    EXIT_NO_DAEMON
+}
+
+/// Sends `req` to the daemon; on failure reports it and yields the exit code.
+fn request(req: &DaemonRequest) -> Result<DaemonResponse, i32> {
+    match control::client::send(req) {
+        Ok(Ok(resp)) => Ok(resp),
+        Ok(Err(e)) => {
+            eprintln!("niri-switch: {e}");
+            Err(exit_code(&e))
+        }
+        Err(e) => {
+            eprintln!("niri-switch: {e}");
+            Err(EXIT_NO_DAEMON)
+        }
+    }
+}
+
+fn print_response(resp: DaemonResponse) {
+    match resp {
+        DaemonResponse::Handled => {}
+        DaemonResponse::OverlayVisible(visible) => {
+            println!("{}", if visible { "visible" } else { "hidden" });
+        }
+        DaemonResponse::Selected(id) => println!("{}", opt_id(id)),
+        DaemonResponse::State(state) => print_state(&state),
+        DaemonResponse::Status(status) => {
+            println!("daemon:     running (pid {}, v{})", status.pid, status.version);
+            println!("overlay:    {}", if status.overlay_visible { "visible" } else { "hidden" });
+            println!("outputs:    {}", status.outputs);
+            println!("workspaces: {} (focused: {})", status.workspaces, opt_id(status.focused_workspace_id));
+            println!("windows:    {} (focused: {})", status.windows, opt_id(status.focused_window_id));
+            println!("niri:       {}", describe_connection(&status.connection));
+            println!("protocol:   {}", describe_compatibility(&status.connection));
+            if status.lagged_events > 0 {
+                println!("lagged:     {} events (resynced from snapshots)", status.lagged_events);
+            }
+        }
+    }
+}
+
+fn print_state_json(state: &NiriState, opts: &StateOptions) -> i32 {
+    let root = match serde_json::to_value(state) {
+        Ok(root) => root,
+        Err(e) => {
+            eprintln!("niri-switch: could not serialize state: {e}");
+            return EXIT_REJECTED;
+        }
+    };
+    let selected = match &opts.path {
+        Some(path) => match path.select(&root) {
+            Ok(values) => values,
+            Err(e) => {
+                eprintln!("niri-switch: {e}");
+                return EXIT_USAGE;
+            }
+        },
+        None => vec![root],
+    };
+
+    for value in selected {
+        match value {
+            serde_json::Value::String(s) if opts.raw => println!("{s}"),
+            value if opts.compact => println!("{value}"),
+            value => println!("{}", serde_json::to_string_pretty(&value).unwrap_or_default()),
+        }
+    }
+    EXIT_OK
+}
+
+fn print_state(state: &NiriState) {
+    if !state.connection.connected {
+        println!("! not connected to niri — {}", describe_connection(&state.connection));
+    }
+    let mut current_output = None;
+    for ws in state.workspaces_in_order() {
+        if ws.output != current_output {
+            current_output = ws.output.clone();
+            let details = state.outputs.iter().find(|o| Some(&o.name) == ws.output.as_ref());
+            print_output_header(ws.output.as_deref(), details);
+        }
+        let marker = if ws.is_focused { '*' } else if ws.is_active { '+' } else { ' ' };
+        let output = ws.output.as_deref().unwrap_or("-");
+        let name = ws.name.as_deref().unwrap_or("");
+        println!("{marker} ws {} (id {}) {name} [{output}]", ws.idx, ws.id);
+        for win in state.windows_by_workspace.get(&ws.id).into_iter().flatten() {
+            let marker = if win.is_focused { '*' } else { ' ' };
+            let pos = match win.layout.pos_in_scrolling_layout {
+                Some((column, tile)) => format!("{column}.{tile}"),
+                None => "float".into(),
+            };
+            println!(
+                "    {marker} {:<6} {pos:<5} {:<24} {}",
+                win.id,
+                win.app_id.as_deref().unwrap_or("?"),
+                win.title.as_deref().unwrap_or(""),
+            );
+        }
+    }
+
+    // Windows niri hasn't placed on a workspace yet are bucketed under 0.
+    for win in state.windows_by_workspace.get(&0).into_iter().flatten() {
+        println!(
+            "  (no workspace) {:<6} {:<24} {}",
+            win.id,
+            win.app_id.as_deref().unwrap_or("?"),
+            win.title.as_deref().unwrap_or(""),
+        );
+    }
+}
+
+fn print_output_header(name: Option<&str>, output: Option<&crate::ipc::types::OutputState>) {
+    let mut line = format!("output {}", name.unwrap_or("-"));
+    if let Some(output) = output {
+        let model = [output.make.as_deref(), output.model.as_deref()]
+        .into_iter().flatten().filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" ");
+        if !model.is_empty() {
+            line.push_str(&format!(" ({model})"));
+        }
+        if let Some(l) = output.logical {
+            line.push_str(&format!(" {}x{} at {},{} scale {}", l.width, l.height, l.x, l.y, l.scale));
+        }
+    }
+    println!("{line}");
+}
+
+fn describe_connection(conn: &ConnectionStatus) -> String {
+    let mut out = if conn.connected {
+        "connected".to_string()
+    } else {
+        let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        match conn.disconnected_since_ms {
+            Some(since) => format!("reconnecting for {}s", now.saturating_sub(since) / 1000),
+            None => "connecting".to_string(),
+        }
+    };
+    if conn.reconnects > 0 {
+        out += &format!(", {} reconnect(s)", conn.reconnects);
+    }
+    if let Some(e) = conn.last_error.as_deref().filter(|_| !conn.connected) {
+        out += &format!(" (last error: {e})");
+    }
+    out
+}
+
+fn describe_compatibility(conn: &ConnectionStatus) -> String {
+    let niri = conn.niri_version.as_deref().unwrap_or("unknown");
+    let verdict = match conn.compatibility {
+        Compatibility::Matched => "compatible",
+        Compatibility::Mismatched => "mismatch, events this build doesn't know are ignored",
+        Compatibility::Unknown => "not checked",
+    };
+    format!("niri {niri}, built against niri-ipc {NIRI_IPC_VERSION} ({verdict})")
+}
+
+fn opt_id(id: Option<u64>) -> String {
+    id.map_or_else(|| "none".into(), |id| id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use niri_ipc::WorkspaceReferenceArg;
+
+    fn parse_str(line: &str) -> Result<Command, String> {
+        parse(&line.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn argv_maps_to_commands() {
+        let send = Command::Send;
+        let daemon = |replace, record: Option<&str>, replay: Option<&str>| Command::Daemon(DaemonOptions {
+            replace,
+            record: record.map(Into::into),
+            replay: replay.map(Into::into),
+        });
+        let cases = [
+            ("",                                daemon(false, None, None)),
+            ("daemon --replace",                daemon(true, None, None)),
+            ("--replace --record /tmp/r.jsonl", daemon(true, Some("/tmp/r.jsonl"), None)),
+            ("daemon --replay r.jsonl",         daemon(false, None, Some("r.jsonl"))),
+            ("help",                            Command::Help),
+            ("-h",                              Command::Help),
+            ("--toggle",                        send(DaemonRequest::Toggle)),
+            ("status",                          send(DaemonRequest::Status)),
+            ("focus 42",                        send(DaemonRequest::FocusWindow { id: 42 })),
+            ("focus-previous",                  send(DaemonRequest::FocusPrevious)),
+            ("cycle-prev",                      send(DaemonRequest::CyclePrev)),
+            ("commit",                          send(DaemonRequest::Commit)),
+            ("move 7 id:3",                     send(DaemonRequest::MoveWindowToWorkspace {
+                window_id: 7,
+                workspace: WorkspaceReferenceArg::Id(3),
+            })),
+            ("workspace web",                   send(DaemonRequest::FocusWorkspace {
+                workspace: WorkspaceReferenceArg::Name("web".into()),
+            })),
+            ("move-to-monitor 7 DP-1",          send(DaemonRequest::MoveWindowToMonitor {
+                window_id: 7,
+                output: "DP-1".into(),
+            })),
+            ("move-workspace idx:2 HDMI-A-1",   send(DaemonRequest::MoveWorkspaceToMonitor {
+                workspace: WorkspaceReferenceArg::Index(2),
+                output: "HDMI-A-1".into(),
+            })),
+            ("close 9",                         send(DaemonRequest::CloseWindow { id: 9 })),
+            ("subscribe",                       Command::Subscribe { events: false }),
+            ("subscribe --events",              Command::Subscribe { events: true }),
+            ("bar",                             Command::Bar { format: bar::DEFAULT_FORMAT.into(), once: false }),
+            ("bar --once --format {title}",     Command::Bar { format: "{title}".into(), once: true }),
+            ("state",                           Command::State(StateOptions::default())),
+            ("state -c",                        Command::State(StateOptions { json: true, compact: true, ..Default::default() })),
+            ("state --json .windows[0] --raw",  Command::State(StateOptions {
+                json: true,
+                raw: true,
+                path: Some(".windows[0]".parse().unwrap()),
+                ..Default::default()
+            })),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_str(line), Ok(expected), "{line}");
+        }
+    }
+
+    #[test]
+    fn bad_arguments_are_usage_errors() {
+        let cases = [
+            ("frobnicate",               "unknown command `frobnicate`"),
+            ("focus",                    "missing window id"),
+            ("focus abc",                "invalid window id `abc`"),
+            ("toggle now",               "unexpected argument `now` for `toggle`"),
+            ("move 7",                   "missing workspace"),
+            ("move-to-monitor 7",        "missing output"),
+            ("move-workspace id:x DP-1", "invalid workspace id `x`"),
+            ("subscribe --state",        "unexpected argument `--state` for `subscribe`"),
+            ("bar --format",             "missing value for --format"),
+            ("bar --verbose",            "unexpected argument `--verbose` for `bar`"),
+            ("daemon --record",          "missing file for --record"),
+            ("--replay",                 "missing file for --replay"),
+            ("daemon now",               "unexpected argument `now` for `daemon`"),
+            ("state --yaml",             "unexpected argument `--yaml` for `state`"),
+            ("state .a .b",              "unexpected argument `.b` for `state`"),
+            ("state .a[",                "unclosed `[` in `.a[`"),
+        ];
+        for (line, error) in cases {
+            assert_eq!(parse_str(line), Err(error.to_string()), "{line}");
+        }
+    }
+
+    #[test]
+    fn daemon_errors_map_to_exit_codes() {
+        let cases = [
+            (CommandError::unavailable("no socket"),                EXIT_NO_NIRI),
+            (CommandError::Timeout { ms: 5000 },                    EXIT_NO_NIRI),
+            (CommandError::WindowGone { id: 1 },                    EXIT_GONE),
+            (CommandError::NiriRejected { reason: "no".into() },    EXIT_REJECTED),
+            (CommandError::UnknownOutput { name: "DP-9".into() },   EXIT_REJECTED),
+            (CommandError::NoPreviousWindow,                        EXIT_REJECTED),
+            (CommandError::Invalid { reason: "bad".into() },        EXIT_REJECTED),
+        ];
+        for (err, code) in cases {
+            assert_eq!(exit_code(&err), code, "{err:?}");
+        }
+        let codes = [EXIT_OK, EXIT_REJECTED, EXIT_USAGE, EXIT_NO_DAEMON, EXIT_NO_NIRI, EXIT_GONE];
+        assert_eq!(codes, [0, 1, 2, 3, 4, 5], "documented in USAGE and the README");
+    }
+}
@@ -0,0 +1,163 @@
+//! Minimal jq-style path selection for `niri-switch state --json <path>`.
+//!
+//! Supports `.field`, `[index]` (negative counts from the end), `["field"]`
+//! and `[]` (iterate), chained: `.workspaces[].name`,
+//! `.windows_by_workspace.3[0].title`. Like jq, a missing field selects
+//! `null` and `[]` fans out into one result per element.
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Field(String),
+    Index(i64),
+    Iter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    steps: Vec<Step>,
+}
+
+impl std::str::FromStr for Path {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, String> {
+        let mut rest = src.strip_prefix('.')
+        .ok_or_else(|| format!("path `{src}` must start with `.`"))?;
+        let mut steps = Vec::new();
+
+        while !rest.is_empty() {
+            if let Some(inner) = rest.strip_prefix('[') {
+                let end = inner.find(']').ok_or_else(|| format!("unclosed `[` in `{src}`"))?;
+                let (body, tail) = (inner[..end].trim(), &inner[end + 1..]);
+                steps.push(if body.is_empty() {
+                    Step::Iter
+                } else if let Some(key) = body.strip_prefix('"').and_then(|b| b.strip_suffix('"')) {
+                    Step::Field(key.to_string())
+                } else {
+                    Step::Index(body.parse().map_err(|_| format!("invalid index `{body}` in `{src}`"))?)
+                });
+                rest = tail.strip_prefix('.').unwrap_or(tail);
+            } else {
+                let end = rest.find(['.', '[']).unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(format!("empty field name in `{src}`"));
+                }
+                steps.push(Step::Field(rest[..end].to_string()));
+                rest = &rest[end..];
+                rest = rest.strip_prefix('.').unwrap_or(rest);
+            }
+        }
+        Ok(Self { steps })
+    }
+}
+
+impl Path {
+    /// Applies the path to `root`, returning every selected value in order.
+    pub fn select(&self, root: &Value) -> Result<Vec<Value>, String> {
+        let mut current = vec![root.clone()];
+        for step in &self.steps {
+            let mut next = Vec::with_capacity(current.len());
+            for value in current {
+                match (step, value) {
+                    (Step::Field(key), Value::Object(mut map)) => {
+                        next.push(map.remove(key).unwrap_or(Value::Null));
+                    }
+                    (Step::Field(_) | Step::Index(_), Value::Null) => next.push(Value::Null),
+                    (Step::Index(i), Value::Array(mut items)) => {
+                        let len = items.len() as i64;
+                        let i = if *i < 0 { len + i } else { *i };
+                        next.push(if (0..len).contains(&i) {
+                            items.swap_remove(i as usize)
+                        } else {
+                            Value::Null
+                        });
+                    }
+                    (Step::Iter, Value::Array(items)) => next.extend(items),
+                    (Step::Iter, Value::Object(map)) => next.extend(map.into_iter().map(|(_, v)| v)),
+                    (step, value) => {
+                        return Err(format!("cannot apply {} to {}", describe(step), kind(&value)));
+                    }
+                }
+            }
+            current = next;
+        }
+        Ok(current)
+    }
+}
+
+fn describe(step: &Step) -> String {
+    match step {
+        Step::Field(key) => format!("field `{key}`"),
+        Step::Index(i) => format!("index [{i}]"),
+        Step::Iter => "[]".into(),
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn select(path: &str, root: &Value) -> Result<Vec<Value>, String> {
+        path.parse::<Path>()?.select(root)
+    }
+
+    #[test]
+    fn paths_select_like_jq() {
+        let root = json!({
+            "windows": [{ "id": 1, "title": "a" }, { "id": 2, "title": "b" }, { "id": 3, "title": "c" }],
+            "by_workspace": { "3": [{ "id": 7 }], "1": [{ "id": 5 }] },
+            "odd key": true,
+        });
+        let cases = [
+            (".windows[0].id",            vec![json!(1)]),
+            (".windows[-1].title",        vec![json!("c")]),
+            (".windows[-3].id",           vec![json!(1)]),
+            (".windows[-4]",              vec![Value::Null]),
+            (".windows[3]",               vec![Value::Null]),
+            (".windows[].id",             vec![json!(1), json!(2), json!(3)]),
+            (".by_workspace.3[0].id",     vec![json!(7)]),
+            (r#".by_workspace["1"][0]"#,  vec![json!({ "id": 5 })]),
+            (r#".["odd key"]"#,           vec![json!(true)]),
+            (".by_workspace[][].id",      vec![json!(5), json!(7)]),
+            (".missing",                  vec![Value::Null]),
+            (".missing.deeper[0]",        vec![Value::Null]),
+            (".windows[].missing",        vec![Value::Null; 3]),
+            (".",                         vec![root.clone()]),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(select(path, &root), Ok(expected), "{path}");
+        }
+    }
+
+    #[test]
+    fn malformed_paths_and_mismatched_steps_are_errors() {
+        let root = json!({ "windows": [{ "id": 1 }], "name": "x" });
+        let cases = [
+            ("windows",       "path `windows` must start with `.`"),
+            (".windows[0",    "unclosed `[` in `.windows[0`"),
+            (".windows[x]",   "invalid index `x` in `.windows[x]`"),
+            (".windows..id",  "empty field name in `.windows..id`"),
+            (".windows.id",   "cannot apply field `id` to an array"),
+            (".name[0]",      "cannot apply index [0] to a string"),
+            (".name[]",       "cannot apply [] to a string"),
+            (".missing[]",    "cannot apply [] to null"),
+        ];
+        for (path, error) in cases {
+            assert_eq!(select(path, &root), Err(error.to_string()), "{path}");
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use std::sync::Arc;
-use tauri::State;
+use tauri::{AppHandle, State};
 use niri_ipc::{Action, WorkspaceReferenceArg};
 
+use crate::error::CommandError;
+use crate::icons::{IconResolver, ICON_SIZE};
 use crate::ipc::{NiriRequest, NiriState};
 
 pub struct AppState {
@@ -11,7 +13,7 @@ pub struct AppState {
 // ── State ─────────────────────────────────────────────────────────────────────
 
 #[tauri::command]
-pub async fn get_state(state: State<'_, AppState>) -> Result<NiriState, String> {
+pub async fn get_state(state: State<'_, AppState>) -> Result<NiriState, CommandError> {
     let s: tokio::sync::RwLockReadGuard<'_, NiriState> = state.client.state.read().await;
     Ok(s.clone())
 }
@@ -19,43 +21,88 @@ pub async fn get_state(state: State<'_, AppState>) -> Result<NiriState, String>
 // ── Window actions ────────────────────────────────────────────────────────────
 
 #[tauri::command]
-pub async fn focus_window(id: u64, state: State<'_, AppState>) -> Result<(), String> {
-    state.client
-    .request(NiriRequest::Action(Action::FocusWindow { id }))
-    .await.map(|_| ()).map_err(|e| e.to_string())
+pub async fn focus_window(id: u64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.client.act_on_window(id, Action::FocusWindow { id }).await
 }
 
 #[tauri::command]
-pub async fn close_window(id: u64, state: State<'_, AppState>) -> Result<(), String> {
-    state.client
-    .request(NiriRequest::Action(Action::CloseWindow { id: Some(id) }))
-    .await.map(|_| ()).map_err(|e| e.to_string())
+pub async fn focus_previous_window(state: State<'_, AppState>) -> Result<(), CommandError> {
+    let previous = state.client.state.read().await.previous_window();
+    let id = previous.ok_or(CommandError::NoPreviousWindow)?;
+    state.client.act_on_window(id, Action::FocusWindow { id }).await
+}
+
+#[tauri::command]
+pub async fn close_window(id: u64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.client.act_on_window(id, Action::CloseWindow { id: Some(id) }).await
 }
 
 #[tauri::command]
 pub async fn move_window_to_workspace(
     window_id: u64,
-    workspace_index: u8,
+    workspace: WorkspaceReferenceArg,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     state.client
-    .request(NiriRequest::Action(Action::MoveWindowToWorkspace {
+    .act_on_window(window_id, Action::MoveWindowToWorkspace {
         window_id: Some(window_id),
-                                 reference: WorkspaceReferenceArg::Index(workspace_index),
-                                 focus: false,
-    }))
-    .await.map(|_| ()).map_err(|e| e.to_string())
+                   reference: workspace,
+                   focus: false,
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn move_window_to_monitor(
+    window_id: u64,
+    output: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let output = state.client.state.read().await.resolve_output(&output)?;
+    state.client
+    .act_on_window(window_id, Action::MoveWindowToMonitor { id: Some(window_id), output })
+    .await
+}
+
+// ── Cycling ───────────────────────────────────────────────────────────────────
+// The overlay calls these on modifier release / Esc; `cycle-next` itself comes
+// from niri binds through the control socket.
+
+#[tauri::command]
+pub async fn cycle_commit(app: AppHandle, state: State<'_, AppState>) -> Result<Option<u64>, CommandError> {
+    crate::cycle::commit(&app, &state.client).await
+}
+
+#[tauri::command]
+pub fn cycle_cancel(app: AppHandle) {
+    crate::cycle::cancel(&app);
 }
 
 // ── Workspace actions ─────────────────────────────────────────────────────────
 
 #[tauri::command]
-pub async fn focus_workspace(index: u8, state: State<'_, AppState>) -> Result<(), String> {
+pub async fn focus_workspace(
+    workspace: WorkspaceReferenceArg,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
     state.client
-    .request(NiriRequest::Action(Action::FocusWorkspace {
-        reference: WorkspaceReferenceArg::Index(index),
+    .request(NiriRequest::Action(Action::FocusWorkspace { reference: workspace }))
+    .await.map(|_| ())
+}
+
+#[tauri::command]
+pub async fn move_workspace_to_monitor(
+    workspace: WorkspaceReferenceArg,
+    output: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let output = state.client.state.read().await.resolve_output(&output)?;
+    state.client
+    .request(NiriRequest::Action(Action::MoveWorkspaceToMonitor {
+        output,
+        reference: Some(workspace),
     }))
-    .await.map(|_| ()).map_err(|e| e.to_string())
+    .await.map(|_| ())
 }
 
 // ── App icon resolution ───────────────────────────────────────────────────────
@@ -64,11 +111,24 @@ pub async fn focus_workspace(index: u8, state: State<'_, AppState>) -> Result<()
 // filesystem access permissions.
 
 #[tauri::command]
-pub async fn get_app_icon(app_id: String) -> Result<String, String> {
+pub async fn get_app_icon(
+    app_id: String,
+    window: tauri::WebviewWindow,
+    icons: State<'_, Arc<IconResolver>>,
+) -> Result<String, CommandError> {
+    // The first lookup in a theme reads all of its directories.
+    let icons = Arc::clone(&icons);
+    let scale = icon_scale(&window);
+    tokio::task::spawn_blocking(move || icon_data_uri(&icons, &app_id, scale))
+    .await
+    .map_err(CommandError::internal)?
+}
+
+fn icon_data_uri(icons: &IconResolver, app_id: &str, scale: u32) -> Result<String, CommandError> {
     use base64::{Engine as _, engine::general_purpose::STANDARD};
 
-    let path = find_icon_path(&app_id)
-    .ok_or_else(|| format!("no icon found for {app_id}"))?;
+    let path = icons.find_icon_path(app_id, ICON_SIZE, scale)
+    .ok_or_else(|| CommandError::IconNotFound { app_id: app_id.to_string() })?;
 
     let ext = path.extension()
     .and_then(|e| e.to_str())
@@ -83,133 +143,16 @@ pub async fn get_app_icon(app_id: String) -> Result<String, String> {
     };
 
     let bytes = std::fs::read(&path)
-    .map_err(|e| format!("failed to read icon {}: {e}", path.display()))?;
+    .map_err(|e| CommandError::internal(format!("failed to read icon {}: {e}", path.display())))?;
 
     let b64 = STANDARD.encode(&bytes);
     Ok(format!("data:{mime};base64,{b64}"))
 }
 
-fn find_icon_path(app_id: &str) -> Option<std::path::PathBuf> {
-    let lower = app_id.to_lowercase();
-    let last = lower.split('.').last().unwrap_or(&lower).to_string();
-    let candidates = [
-        lower.clone(),
-        last.clone(),
-        last.replace("-desktop", ""),
-        last.replace("-bin", ""),
-    ];
-
-    let home = std::env::var("HOME").unwrap_or_default();
-    let data_dirs = std::env::var("XDG_DATA_DIRS")
-    .unwrap_or_else(|_| "/usr/local/share:/usr/share".into());
-
-    let mut icon_bases: Vec<std::path::PathBuf> = vec![
-        std::path::PathBuf::from(format!("{home}/.local/share/icons")),
-        std::path::PathBuf::from(format!("{home}/.icons")),
-    ];
-    for d in data_dirs.split(':') {
-        icon_bases.push(std::path::PathBuf::from(format!("{d}/icons")));
-    }
-    icon_bases.push(std::path::PathBuf::from("/usr/share/pixmaps"));
-
-    // Size preference: scalable SVGs first, then largest raster sizes
-    let size_dirs = [
-        "scalable/apps",
-        "256x256/apps", "256x256@2/apps",
-        "128x128/apps", "128x128@2/apps",
-        "64x64/apps",
-        "48x48/apps",
-        "32x32/apps",
-    ];
-    let themes = ["hicolor", "breeze", "Adwaita", "AdwaitaLegacy", "Papirus", "gnome"];
-    let extensions = ["svg", "png", "xpm"];
-
-    for candidate in &candidates {
-        for base in &icon_bases {
-            if !base.exists() { continue; }
-            for theme in &themes {
-                for size_dir in &size_dirs {
-                    for ext in &extensions {
-                        let p = base.join(theme).join(size_dir)
-                        .join(format!("{candidate}.{ext}"));
-                        if p.exists() { return Some(p); }
-                    }
-                }
-            }
-            // Flat pixmaps dir
-            for ext in &extensions {
-                let p = base.join(format!("{candidate}.{ext}"));
-                if p.exists() { return Some(p); }
-            }
-        }
-
-        // Parse .desktop file for Icon= field
-        if let Some(icon_name) = find_desktop_icon(candidate) {
-            if icon_name != *candidate {
-                if let Some(p) = find_icon_path(&icon_name) {
-                    return Some(p);
-                }
-            }
-        }
-    }
-    None
-}
-
-/// Parse the Icon= field from a .desktop file for the given app name.
-fn find_desktop_icon(app_name: &str) -> Option<String> {
-    let data_dirs = std::env::var("XDG_DATA_DIRS")
-    .unwrap_or_else(|_| "/usr/local/share:/usr/share".into());
-    let home = std::env::var("HOME").unwrap_or_default();
-
-    let mut search_dirs = vec![
-        format!("{home}/.local/share/applications"),
-    ];
-    for d in data_dirs.split(':') {
-        search_dirs.push(format!("{d}/applications"));
-    }
-
-    for dir in search_dirs {
-        // Try exact name and name.desktop
-        for filename in [
-            format!("{app_name}.desktop"),
-                format!("{}.desktop", app_name.to_lowercase()),
-        ] {
-            let path = std::path::Path::new(&dir).join(&filename);
-            if let Ok(content) = std::fs::read_to_string(&path) {
-                for line in content.lines() {
-                    if let Some(icon) = line.strip_prefix("Icon=") {
-                        let icon = icon.trim().to_string();
-                        if !icon.is_empty() {
-                            return Some(icon);
-                        }
-                    }
-                }
-            }
-        }
-        // Also scan all .desktop files for matching Exec= or Name=
-        if let Ok(entries) = std::fs::read_dir(&dir) {
-            for entry in entries.flatten() {
-                let p = entry.path();
-                if p.extension().and_then(|e| e.to_str()) != Some("desktop") { continue; }
-                let Ok(content) = std::fs::read_to_string(&p) else { continue };
-                let mut icon_val = None;
-                let mut matches = false;
-                for line in content.lines() {
-                    if let Some(v) = line.strip_prefix("Icon=") { icon_val = Some(v.trim().to_string()); }
-                    if let Some(v) = line.strip_prefix("Exec=") {
-                        if v.to_lowercase().contains(app_name) { matches = true; }
-                    }
-                    if let Some(v) = line.strip_prefix("StartupWMClass=") {
-                        if v.to_lowercase() == app_name { matches = true; }
-                    }
-                }
-                if matches {
-                    if let Some(icon) = icon_val { return Some(icon); }
-                }
-            }
-        }
-    }
-    None
+/// Icon themes only come in integer scales; round fractional ones up so
+/// icons are downscaled rather than blurred.
+fn icon_scale(window: &tauri::WebviewWindow) -> u32 {
+    window.scale_factor().map_or(1, |scale| scale.ceil().max(1.0) as u32)
 }
 
 // ── Icon debug helper ─────────────────────────────────────────────────────────
@@ -217,70 +160,198 @@ fn find_desktop_icon(app_name: &str) -> Option<String> {
 // to see exactly where it searched and what it found/missed.
 
 #[tauri::command]
-pub async fn debug_icon_search(app_id: String) -> Result<Vec<String>, String> {
+pub async fn debug_icon_search(
+    app_id: String,
+    window: tauri::WebviewWindow,
+    icons: State<'_, Arc<IconResolver>>,
+) -> Result<Vec<String>, CommandError> {
     let lower = app_id.to_lowercase();
-    let last = lower.split('.').last().unwrap_or(&lower).to_string();
+    let last = lower.split('.').next_back().unwrap_or(&lower).to_string();
 
     let home = std::env::var("HOME").unwrap_or_default();
     let data_dirs = std::env::var("XDG_DATA_DIRS")
     .unwrap_or_else(|_| "/usr/local/share:/usr/share".into());
+    let scale = icon_scale(&window);
 
     let mut searched = vec![
         format!("HOME={home}"),
             format!("XDG_DATA_DIRS={data_dirs}"),
                 format!("candidates: {lower}, {last}"),
+                format!("size: {ICON_SIZE}@{scale}"),
     ];
 
-    let mut icon_bases: Vec<std::path::PathBuf> = vec![
-        std::path::PathBuf::from(format!("{home}/.local/share/icons")),
-        std::path::PathBuf::from(format!("{home}/.icons")),
-    ];
-    for d in data_dirs.split(':') {
-        icon_bases.push(std::path::PathBuf::from(format!("{d}/icons")));
-        icon_bases.push(std::path::PathBuf::from(format!("{d}/pixmaps")));
-    }
-    icon_bases.push(std::path::PathBuf::from("/usr/share/pixmaps"));
-
     // Report which base dirs actually exist
-    for base in &icon_bases {
+    for base in icons.themes().base_dirs() {
         searched.push(format!("base {} exists={}", base.display(), base.exists()));
     }
 
-    // List actual theme dirs inside each base that exists
-    for base in &icon_bases {
-        if !base.exists() { continue; }
-        if let Ok(entries) = std::fs::read_dir(base) {
-            let themes: Vec<String> = entries
-            .flatten()
-            .filter(|e| e.path().is_dir())
-            .map(|e| e.file_name().to_string_lossy().into_owned())
-            .collect();
-            searched.push(format!("themes in {}: {:?}", base.display(), themes));
-        }
+    match icons.configured() {
+        Some(theme) => searched.push(format!("configured theme: {} (from {})", theme.name, theme.source)),
+        None => searched.push("configured theme: none".into()),
+    }
+
+    // The themes searched, each followed by what it inherits from
+    for theme in icons.preferred().iter().map(String::as_str).chain(["hicolor"]) {
+        searched.push(format!("theme chain: {}", icons.themes().chain(theme).join(" → ")));
     }
 
-    // Check .desktop files
-    let mut desktop_dirs = vec![format!("{home}/.local/share/applications")];
-    for d in data_dirs.split(':') {
-        desktop_dirs.push(format!("{d}/applications"));
+    // Which desktop entries the app id and its last segment match
+    let desktop = icons.desktop_entries();
+    for dir in desktop.dirs() {
+        searched.push(format!("desktop dir {} exists={}", dir.display(), dir.exists()));
     }
-    for dir in &desktop_dirs {
-        let p = std::path::Path::new(dir);
-        if p.exists() {
-            searched.push(format!("desktop dir exists: {dir}"));
-            // Try to find app_id desktop file
-            for name in [format!("{last}.desktop"), format!("{lower}.desktop")] {
-                let fp = p.join(&name);
-                if fp.exists() {
-                    let content = std::fs::read_to_string(&fp).unwrap_or_default();
-                    let icon_line = content.lines()
-                    .find(|l| l.starts_with("Icon="))
-                    .unwrap_or("Icon= NOT FOUND");
-                    searched.push(format!("found {name}: {icon_line}"));
-                }
-            }
+    let index = desktop.index();
+    searched.push(format!("desktop entries indexed: {}", index.len()));
+    for key in [&app_id, &last] {
+        match index.lookup(key) {
+            Some((entry, matched)) => searched.push(format!(
+                "{key}: {} by {matched:?}, Icon={}",
+                entry.path.display(),
+                entry.icon.as_deref().unwrap_or("NOT FOUND"),
+            )),
+            None => searched.push(format!("{key}: no desktop entry")),
         }
     }
 
+    match icons.find_icon_path(&app_id, ICON_SIZE, scale) {
+        Some(path) => searched.push(format!("resolved: {}", path.display())),
+        None => searched.push("resolved: nothing".into()),
+    }
     Ok(searched)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::mock::{window, workspace, MockNiri};
+    use crate::ipc::reducer::reduce;
+    use crate::ipc::{NiriClient, NiriEvent};
+    use tauri::Manager;
+
+    fn mock_app() -> tauri::App<tauri::test::MockRuntime> {
+        let (client, _rx) = NiriClient::new();
+        tauri::test::mock_builder()
+        .manage(AppState { client: Arc::new(client) })
+        .build(tauri::test::mock_context(tauri::test::noop_assets()))
+        .expect("failed to build mock app")
+    }
+
+    async fn seed(app: &tauri::App<tauri::test::MockRuntime>, windows: Vec<niri_ipc::Window>) {
+        let mut state = app.state::<AppState>().client.state.write().await;
+        reduce(&mut state, &NiriEvent::WorkspacesChanged { workspaces: vec![workspace(1, 1)] });
+        reduce(&mut state, &NiriEvent::WindowsChanged { windows });
+    }
+
+    #[tokio::test]
+    async fn window_commands_send_niri_actions() {
+        let niri = MockNiri::start().await;
+        let app = mock_app();
+        seed(&app, vec![window(7, 1), window(8, 1), window(9, 1)]).await;
+
+        focus_window(7, app.state()).await.unwrap();
+        close_window(8, app.state()).await.unwrap();
+        move_window_to_workspace(9, WorkspaceReferenceArg::Id(2), app.state()).await.unwrap();
+
+        let requests = niri.requests();
+        assert!(matches!(
+            requests[..],
+            [
+                NiriRequest::Action(Action::FocusWindow { id: 7 }),
+                NiriRequest::Action(Action::CloseWindow { id: Some(8) }),
+                NiriRequest::Action(Action::MoveWindowToWorkspace {
+                    window_id: Some(9),
+                    reference: WorkspaceReferenceArg::Id(2),
+                    focus: false,
+                }),
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn commands_report_niri_errors() {
+        let _niri = MockNiri::with_responder(|_| Err("no such workspace".into())).await;
+        let app = mock_app();
+
+        let err = focus_workspace(WorkspaceReferenceArg::Id(5), app.state()).await.unwrap_err();
+
+        assert_eq!(err, CommandError::NiriRejected { reason: "no such workspace".into() });
+    }
+
+    #[tokio::test]
+    async fn actions_on_closed_windows_fail_without_reaching_niri() {
+        let niri = MockNiri::start().await;
+        let app = mock_app();
+        seed(&app, vec![window(7, 1)]).await;
+
+        let err = close_window(8, app.state()).await.unwrap_err();
+
+        assert_eq!(err, CommandError::WindowGone { id: 8 });
+        assert_eq!(serde_json::to_value(&err).unwrap(), serde_json::json!({ "kind": "window_gone", "id": 8 }));
+        assert!(niri.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn monitor_moves_only_target_known_outputs() {
+        let niri = MockNiri::start().await;
+        let app = mock_app();
+        seed(&app, vec![window(9, 1)]).await;
+
+        let err = move_window_to_monitor(9, "HDMI-A-1".into(), app.state()).await.unwrap_err();
+        assert_eq!(err, CommandError::UnknownOutput { name: "HDMI-A-1".into() });
+        move_window_to_monitor(9, "mock-1".into(), app.state()).await.unwrap();
+        move_workspace_to_monitor(WorkspaceReferenceArg::Id(1), "MOCK-1".into(), app.state()).await.unwrap();
+
+        let requests = niri.requests();
+        assert!(matches!(
+            &requests[..],
+            [
+                NiriRequest::Action(Action::MoveWindowToMonitor { id: Some(9), output: a }),
+                NiriRequest::Action(Action::MoveWorkspaceToMonitor {
+                    output: b,
+                    reference: Some(WorkspaceReferenceArg::Id(1)),
+                }),
+            ] if a == "MOCK-1" && b == "MOCK-1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn focus_previous_window_follows_focus_history() {
+        let niri = MockNiri::start().await;
+        let app = mock_app();
+
+        let err = focus_previous_window(app.state()).await.unwrap_err();
+        assert_eq!(err, CommandError::NoPreviousWindow);
+
+        {
+            let mut state = app.state::<AppState>().client.state.write().await;
+            let mut focused = window(11, 1);
+            focused.is_focused = true;
+            reduce(&mut state, &NiriEvent::WindowsChanged { windows: vec![window(10, 1), focused, window(12, 1)] });
+            reduce(&mut state, &NiriEvent::WindowFocusChanged { id: Some(12) });
+        }
+        focus_previous_window(app.state()).await.unwrap();
+
+        assert!(matches!(niri.requests()[..], [NiriRequest::Action(Action::FocusWindow { id: 11 })]));
+    }
+
+    #[tokio::test]
+    async fn get_state_returns_the_client_state() {
+        let niri = MockNiri::start().await;
+        niri.set_initial_events(vec![
+            NiriEvent::WorkspacesChanged { workspaces: vec![workspace(1, 1)] },
+            NiriEvent::WindowsChanged { windows: vec![window(10, 1)] },
+        ]);
+        let app = mock_app();
+        let client = Arc::clone(&app.state::<AppState>().client);
+        tokio::spawn(Arc::clone(&client).run_event_stream());
+
+        let c = &client;
+        crate::ipc::mock::eventually("initial state", || async move {
+            c.state.read().await.total_windows() == 1
+        }).await;
+        let state = get_state(app.state()).await.unwrap();
+
+        assert_eq!(state.focused_workspace_id, Some(1));
+        assert_eq!(state.windows_by_workspace[&1][0].id, 10);
+    }
+}
@@ -0,0 +1,53 @@
+use std::io::{BufRead, BufReader, Lines, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use super::protocol::{socket_path, DaemonReply, DaemonRequest};
+use crate::error::CommandError;
+
+/// Upper bound on waiting for a one-shot reply, so a wedged daemon can't hang
+/// a keybind or script forever.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends one request to the running daemon and waits for its reply.
+///
+/// Blocking on purpose: the CLI side of niri-switch runs before (and without)
+/// any async runtime.
+pub fn send(req: &DaemonRequest) -> anyhow::Result<DaemonReply> {
+    let (stream, mut reader) = open(req)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    read_reply(&mut reader)
+}
+
+/// Sends a `Subscribe*` request and, once the daemon accepts it, returns the
+/// JSON lines that follow. The iterator ends when the daemon goes away.
+pub fn subscribe(
+    req: &DaemonRequest,
+) -> anyhow::Result<Result<Lines<BufReader<UnixStream>>, CommandError>> {
+    // The write half stays open: closing it tells the daemon we've gone.
+    let (_stream, mut reader) = open(req)?;
+    Ok(read_reply(&mut reader)?.map(|_| reader.lines()))
+}
+
+fn open(req: &DaemonRequest) -> anyhow::Result<(UnixStream, BufReader<UnixStream>)> {
+    let path = socket_path();
+    let mut stream = UnixStream::connect(&path)
+    .map_err(|e| anyhow::anyhow!("daemon not reachable at {}: {e}", path.display()))?;
+
+    let mut payload = serde_json::to_vec(req)?;
+    payload.push(b'\n');
+    stream.write_all(&payload)?;
+
+    let reader = BufReader::new(stream.try_clone()?);
+    Ok((stream, reader))
+}
+
+fn read_reply(reader: &mut BufReader<UnixStream>) -> anyhow::Result<DaemonReply> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.is_empty() {
+        anyhow::bail!("daemon closed the connection without replying");
+    }
+    Ok(serde_json::from_str(line.trim())?)
+}
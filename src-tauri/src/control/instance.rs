@@ -0,0 +1,155 @@
+//! Single-instance guard for the control socket.
+//!
+//! A daemon only takes over `niri-switch.sock` after making sure nobody is
+//! answering on it, so a second launch (e.g. a systemd restart racing a manual
+//! start) can't silently steal the socket from a live daemon. The socket is
+//! bound right after the check, and whoever binds first keeps it.
+
+use std::io::ErrorKind;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use super::client;
+use super::protocol::{socket_path, DaemonRequest, DaemonResponse, DaemonStatus};
+
+/// How long `--replace` waits for the old daemon to let go of the socket.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What currently sits behind the control socket path.
+pub enum Probe {
+    /// No socket file at all.
+    Absent,
+    /// A socket file nobody is listening on (left over from a crash).
+    Stale,
+    /// A daemon accepted the connection. Its status, if it answered in time.
+    Live(Option<DaemonStatus>),
+}
+
+pub fn probe() -> Probe {
+    let path = socket_path();
+    if UnixStream::connect(&path).is_err() {
+        return if path.exists() { Probe::Stale } else { Probe::Absent };
+    }
+    match client::send(&DaemonRequest::Status) {
+        Ok(Ok(DaemonResponse::Status(status))) => Probe::Live(Some(status)),
+        _ => Probe::Live(None),
+    }
+}
+
+/// Binds the control socket for this process.
+///
+/// Stale sockets are removed. A live daemon is left alone unless `replace`
+/// is set, in which case it is asked to quit and given a moment to exit.
+pub fn claim(replace: bool) -> Result<UnixListener, String> {
+    let path = socket_path();
+    match probe() {
+        Probe::Absent => {}
+        Probe::Stale => {
+            info!("Removing stale control socket {}", path.display());
+            remove_socket(&path)?;
+        }
+        Probe::Live(status) if !replace => {
+            let pid = status.map(|s| format!(" (pid {})", s.pid)).unwrap_or_default();
+            return Err(format!(
+                "niri-switch is already running{pid}; \
+                 use `niri-switch toggle` to drive it or `--replace` to take over"
+            ));
+        }
+        Probe::Live(_) => {
+            info!("Asking the running daemon to quit");
+            let _ = client::send(&DaemonRequest::Quit);
+            let started = Instant::now();
+            while UnixStream::connect(&path).is_ok() {
+                if started.elapsed() > REPLACE_TIMEOUT {
+                    return Err("the running daemon did not quit; not replacing it".into());
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            remove_socket(&path)?;
+        }
+    }
+    // Bound here rather than once the daemon is up, so the path is never
+    // free for long: a daemon that started while we probed wins, and we stop.
+    UnixListener::bind(&path).map_err(|e| match e.kind() {
+        ErrorKind::AddrInUse => "another niri-switch daemon started at the same time".to_string(),
+        _ => format!("could not bind control socket {}: {e}", path.display()),
+    })
+}
+
+fn remove_socket(path: &Path) -> Result<(), String> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(format!("could not remove stale socket {}: {e}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points `socket_path()` into a fresh directory until dropped.
+    struct RuntimeDir {
+        dir: std::path::PathBuf,
+        previous: Option<std::ffi::OsString>,
+        _env: tokio::sync::MutexGuard<'static, ()>,
+    }
+
+    impl RuntimeDir {
+        fn new(name: &str) -> Self {
+            let env = crate::ipc::mock::ENV_LOCK.blocking_lock();
+            let dir = std::env::temp_dir().join(format!("niri-switch-instance-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let previous = std::env::var_os("XDG_RUNTIME_DIR");
+            std::env::set_var("XDG_RUNTIME_DIR", &dir);
+            Self { dir, previous, _env: env }
+        }
+    }
+
+    impl Drop for RuntimeDir {
+        fn drop(&mut self) {
+            match self.previous.take() {
+                Some(previous) => std::env::set_var("XDG_RUNTIME_DIR", previous),
+                None => std::env::remove_var("XDG_RUNTIME_DIR"),
+            }
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn claims_a_missing_socket() {
+        let _dir = RuntimeDir::new("missing");
+        assert!(matches!(probe(), Probe::Absent));
+
+        let _listener = claim(false).unwrap();
+        assert!(socket_path().exists());
+    }
+
+    #[test]
+    fn replaces_a_stale_socket() {
+        let _dir = RuntimeDir::new("stale");
+        drop(UnixListener::bind(socket_path()).unwrap());
+        assert!(matches!(probe(), Probe::Stale));
+
+        let listener = claim(false).unwrap();
+        let _client = UnixStream::connect(socket_path()).unwrap();
+        assert!(listener.accept().is_ok(), "the new socket is the one listening");
+    }
+
+    #[test]
+    fn leaves_a_live_daemon_alone() {
+        let _dir = RuntimeDir::new("live");
+        let live = UnixListener::bind(socket_path()).unwrap();
+        // Hangs up on everyone, like a daemon too busy to answer.
+        std::thread::spawn(move || live.incoming().for_each(drop));
+        assert!(matches!(probe(), Probe::Live(None)));
+
+        let err = claim(false).unwrap_err();
+        assert!(err.contains("already running"), "{err}");
+        assert!(socket_path().exists());
+    }
+}
@@ -0,0 +1,6 @@
+pub mod client;
+pub mod instance;
+pub mod protocol;
+pub(crate) mod server;
+
+pub use protocol::{socket_path, DaemonReply, DaemonRequest, DaemonResponse};
@@ -0,0 +1,240 @@
+//! Line protocol spoken on the daemon control socket.
+//!
+//! A client writes one request per line, either as JSON in the same
+//! externally-tagged shape niri uses (`"Toggle"`, `{"FocusWindow":{"id":42}}`)
+//! or as a plain verb with arguments (`toggle`, `focus-window 42`). The daemon
+//! answers every request with exactly one JSON [`DaemonReply`] line, so a
+//! connection can carry several requests in a row.
+
+use niri_ipc::WorkspaceReferenceArg;
+use serde::{Deserialize, Serialize};
+
+use crate::error::CommandError;
+use crate::ipc::{ConnectionStatus, NiriState};
+
+// ── Socket path ───────────────────────────────────────────────────────────────
+
+pub fn socket_path() -> std::path::PathBuf {
+    // Derive the path from the real UID so it's consistent whether called
+    // from a terminal (XDG_RUNTIME_DIR set) or systemd service (may not be set).
+    // /run/user/<uid> is always available on systemd-based Linux regardless of env.
+    let runtime = std::env::var("XDG_RUNTIME_DIR")
+    .unwrap_or_else(|_| format!("/run/user/{}", crate::ipc::types::read_uid()));
+    std::path::PathBuf::from(format!("{runtime}/niri-switch.sock"))
+}
+
+// ── Requests ──────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DaemonRequest {
+    /// Show the overlay and give it keyboard focus.
+    Show,
+    /// Hide the overlay.
+    Hide,
+    /// Show the overlay if hidden, hide it otherwise.
+    Toggle,
+    /// Ask niri to focus a window.
+    FocusWindow { id: u64 },
+    /// Focus the window that was focused before the current one.
+    FocusPrevious,
+    /// Move the alt-tab selection to the next window in MRU order, showing
+    /// the overlay and starting a cycle if none is running.
+    CycleNext,
+    /// Like `CycleNext`, but backwards.
+    CyclePrev,
+    /// Focus the selected window, end the cycle and hide the overlay.
+    Commit,
+    /// End the cycle without changing focus and hide the overlay.
+    Cancel,
+    /// Ask niri to move a window to a workspace.
+    MoveWindowToWorkspace { window_id: u64, workspace: WorkspaceReferenceArg },
+    /// Ask niri to focus a workspace.
+    FocusWorkspace { workspace: WorkspaceReferenceArg },
+    /// Ask niri to move a window to another output, by connector name.
+    MoveWindowToMonitor { window_id: u64, output: String },
+    /// Ask niri to move a workspace, windows and all, to another output.
+    MoveWorkspaceToMonitor { workspace: WorkspaceReferenceArg, output: String },
+    /// Ask niri to close a window.
+    CloseWindow { id: u64 },
+    /// The daemon's aggregated niri state.
+    State,
+    /// Daemon liveness and a short summary of what it tracks.
+    Status,
+    /// Drop the niri event stream and reconnect, re-seeding the full state.
+    Reload,
+    /// Shut the daemon down (used by `niri-switch --replace`).
+    Quit,
+    /// Turn this connection into a stream of `NiriState` snapshots, one JSON
+    /// line whenever the state changes (plus the current state right away).
+    Subscribe,
+    /// Turn this connection into a stream of raw niri events as JSON lines.
+    SubscribeEvents,
+}
+
+// ── Replies ───────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DaemonResponse {
+    /// The request was carried out and has nothing to report.
+    Handled,
+    /// Whether the overlay is visible after the request was carried out.
+    OverlayVisible(bool),
+    /// The window selected by a cycle request (for `Commit`, the window that
+    /// was focused); `None` when there was nothing to select.
+    Selected(Option<u64>),
+    /// Reply to [`DaemonRequest::State`]. Boxed: it dwarfs every other reply.
+    State(Box<NiriState>),
+    /// Reply to [`DaemonRequest::Status`].
+    Status(DaemonStatus),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub version: String,
+    pub overlay_visible: bool,
+    pub outputs: usize,
+    pub workspaces: usize,
+    pub windows: usize,
+    pub focused_workspace_id: Option<u64>,
+    pub focused_window_id: Option<u64>,
+    /// Whether the daemon is currently connected to niri's event stream.
+    pub connection: ConnectionStatus,
+    /// niri events that the overlay or a subscriber fell too far behind to
+    /// receive; each gap was filled with a full-state snapshot.
+    pub lagged_events: u64,
+}
+
+/// Shaped like `niri_ipc::Reply`: `{"Ok": ...}` on success, otherwise
+/// `{"Err": {"kind": ...}}` carrying a [`CommandError`].
+pub type DaemonReply = Result<DaemonResponse, CommandError>;
+
+// ── Plain-verb parsing ────────────────────────────────────────────────────────
+
+impl DaemonRequest {
+    /// Parses a single request line in either JSON or plain-verb form.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        if line.starts_with('{') || line.starts_with('"') {
+            return serde_json::from_str(line).map_err(|e| format!("malformed request: {e}"));
+        }
+
+        let mut args = line.split_whitespace();
+        let verb = args.next().ok_or("empty request")?;
+        let req = match verb {
+            "show"                      => Self::Show,
+            "hide"                      => Self::Hide,
+            "toggle"                    => Self::Toggle,
+            "focus-window"              => Self::FocusWindow { id: parse_arg(args.next(), "window id")? },
+            "focus-previous"            => Self::FocusPrevious,
+            "cycle-next"                => Self::CycleNext,
+            "cycle-prev"                => Self::CyclePrev,
+            "commit"                    => Self::Commit,
+            "cancel"                    => Self::Cancel,
+            "move-window"               => Self::MoveWindowToWorkspace {
+                window_id: parse_arg(args.next(), "window id")?,
+                workspace: parse_workspace(args.next().ok_or("missing workspace")?)?,
+            },
+            "focus-workspace"           => Self::FocusWorkspace {
+                workspace: parse_workspace(args.next().ok_or("missing workspace")?)?,
+            },
+            "move-window-to-monitor"    => Self::MoveWindowToMonitor {
+                window_id: parse_arg(args.next(), "window id")?,
+                output: args.next().ok_or("missing output")?.into(),
+            },
+            "move-workspace-to-monitor" => Self::MoveWorkspaceToMonitor {
+                workspace: parse_workspace(args.next().ok_or("missing workspace")?)?,
+                output: args.next().ok_or("missing output")?.into(),
+            },
+            "close-window"              => Self::CloseWindow { id: parse_arg(args.next(), "window id")? },
+            "state"                     => Self::State,
+            "status"                    => Self::Status,
+            "reload"                    => Self::Reload,
+            "quit"                      => Self::Quit,
+            "subscribe"                 => match args.next() {
+                None           => Self::Subscribe,
+                Some("events") => Self::SubscribeEvents,
+                Some(other)    => return Err(format!("unknown subscription `{other}`")),
+            },
+            other                       => return Err(format!("unknown command `{other}`")),
+        };
+        if let Some(extra) = args.next() {
+            return Err(format!("unexpected argument `{extra}` for `{verb}`"));
+        }
+        Ok(req)
+    }
+}
+
+/// Parses a workspace reference: `id:<id>`, `idx:<index>`, `name:<name>`,
+/// or, like niri's own CLI, a bare number as an index and anything else as a
+/// name. Indices are relative to the focused output, so multi-monitor
+/// callers should prefer ids.
+pub fn parse_workspace(arg: &str) -> Result<WorkspaceReferenceArg, String> {
+    match arg.split_once(':') {
+        Some(("id", id)) => id.parse().map(WorkspaceReferenceArg::Id)
+        .map_err(|_| format!("invalid workspace id `{id}`")),
+        Some(("idx" | "index", idx)) => idx.parse().map(WorkspaceReferenceArg::Index)
+        .map_err(|_| format!("invalid workspace index `{idx}`")),
+        Some(("name", name)) if !name.is_empty() => Ok(WorkspaceReferenceArg::Name(name.into())),
+        _ => arg.parse().map_err(|e: &str| e.to_string()),
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<&str>, what: &str) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("missing {what}"))?;
+    arg.parse().map_err(|_| format!("invalid {what} `{arg}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_verbs_and_json_parse_to_the_same_request() {
+        let cases = [
+            ("toggle",                           r#""Toggle""#,                         DaemonRequest::Toggle),
+            ("focus-window 42",                  r#"{"FocusWindow":{"id":42}}"#,        DaemonRequest::FocusWindow { id: 42 }),
+            ("  subscribe events ",              r#""SubscribeEvents""#,                DaemonRequest::SubscribeEvents),
+            ("move-window 7 id:3",               r#"{"MoveWindowToWorkspace":{"window_id":7,"workspace":{"Id":3}}}"#,
+             DaemonRequest::MoveWindowToWorkspace { window_id: 7, workspace: WorkspaceReferenceArg::Id(3) }),
+            ("move-workspace-to-monitor name:web DP-1",
+             r#"{"MoveWorkspaceToMonitor":{"workspace":{"Name":"web"},"output":"DP-1"}}"#,
+             DaemonRequest::MoveWorkspaceToMonitor { workspace: WorkspaceReferenceArg::Name("web".into()), output: "DP-1".into() }),
+        ];
+        for (verb, json, expected) in cases {
+            assert_eq!(DaemonRequest::parse(verb), Ok(expected.clone()), "{verb}");
+            assert_eq!(DaemonRequest::parse(json), Ok(expected), "{json}");
+        }
+    }
+
+    #[test]
+    fn missing_invalid_and_extra_arguments_are_rejected() {
+        let cases = [
+            ("",                         "empty request"),
+            ("focus-window",             "missing window id"),
+            ("focus-window abc",         "invalid window id `abc`"),
+            ("move-window 7",            "missing workspace"),
+            ("move-window-to-monitor 7", "missing output"),
+            ("focus-workspace id:x",     "invalid workspace id `x`"),
+            ("toggle now",               "unexpected argument `now` for `toggle`"),
+            ("focus-window 1 2",         "unexpected argument `2` for `focus-window`"),
+            ("subscribe windows",        "unknown subscription `windows`"),
+            ("frobnicate",               "unknown command `frobnicate`"),
+        ];
+        for (line, error) in cases {
+            assert_eq!(DaemonRequest::parse(line), Err(error.to_string()), "{line}");
+        }
+        assert!(DaemonRequest::parse(r#"{"FocusWindow":{}}"#).unwrap_err().starts_with("malformed request"));
+    }
+
+    #[test]
+    fn workspace_references_take_a_prefix_or_guess_like_niri() {
+        assert_eq!(parse_workspace("id:5"), Ok(WorkspaceReferenceArg::Id(5)));
+        assert_eq!(parse_workspace("idx:2"), Ok(WorkspaceReferenceArg::Index(2)));
+        assert_eq!(parse_workspace("index:2"), Ok(WorkspaceReferenceArg::Index(2)));
+        assert_eq!(parse_workspace("name:id:5"), Ok(WorkspaceReferenceArg::Name("id:5".into())));
+        assert_eq!(parse_workspace("3"), Ok(WorkspaceReferenceArg::Index(3)));
+        assert_eq!(parse_workspace("web"), Ok(WorkspaceReferenceArg::Name("web".into())));
+        assert_eq!(parse_workspace("idx:-1"), Err("invalid workspace index `-1`".to_string()));
+    }
+}
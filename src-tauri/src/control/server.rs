@@ -0,0 +1,220 @@
+use std::sync::Arc;
+use niri_ipc::Action;
+use serde::Serialize;
+use tauri::AppHandle;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixListener, UnixStream,
+    },
+    sync::broadcast::error::RecvError,
+};
+use tracing::{info, warn};
+
+use super::protocol::{socket_path, DaemonReply, DaemonRequest, DaemonResponse, DaemonStatus};
+use crate::error::CommandError;
+use crate::ipc::{NiriClient, NiriEvent, NiriRequest, Received};
+
+/// Serves requests on the control socket `instance::claim()` bound, until
+/// the daemon exits.
+pub async fn serve(listener: std::os::unix::net::UnixListener, app: AppHandle, client: Arc<NiriClient>) {
+    let listener = match listener.set_nonblocking(true).and_then(|()| UnixListener::from_std(listener)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Failed to listen on control socket: {e}");
+            return;
+        }
+    };
+    info!("Control socket listening at {}", socket_path().display());
+
+    loop {
+        let Ok((stream, _)) = listener.accept().await else { continue };
+        let app = app.clone();
+        let client = Arc::clone(&client);
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = handle_connection(stream, &app, &client).await {
+                warn!("Control connection error: {e}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    app: &AppHandle,
+    client: &NiriClient,
+) -> anyhow::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() { continue; }
+        let reply = match DaemonRequest::parse(&line) {
+            // Like niri's EventStream, a subscription acknowledges once and
+            // then owns the connection until the client goes away.
+            Ok(DaemonRequest::Subscribe) => {
+                write_line(&mut write, &DaemonReply::Ok(DaemonResponse::Handled)).await?;
+                return stream_snapshots(lines, write, client).await;
+            }
+            Ok(DaemonRequest::SubscribeEvents) => {
+                write_line(&mut write, &DaemonReply::Ok(DaemonResponse::Handled)).await?;
+                return stream_events(lines, write, client).await;
+            }
+            Ok(DaemonRequest::Quit) => {
+                write_line(&mut write, &DaemonReply::Ok(DaemonResponse::Handled)).await?;
+                write.flush().await?;
+                info!("Quit requested over the control socket");
+                app.exit(0);
+                return Ok(());
+            }
+            Ok(req) => dispatch(req, app, client).await,
+            Err(e) => Err(e.into()),
+        };
+        write_line(&mut write, &reply).await?;
+    }
+    Ok(())
+}
+
+async fn write_line<T: Serialize>(write: &mut OwnedWriteHalf, value: &T) -> anyhow::Result<()> {
+    let mut payload = serde_json::to_vec(value)?;
+    payload.push(b'\n');
+    write.write_all(&payload).await?;
+    Ok(())
+}
+
+// ── Subscriptions ─────────────────────────────────────────────────────────────
+// Both streams ride on the same broadcast channel that feeds the webview, so
+// external widgets see exactly what the overlay sees.
+
+/// Writes the full `NiriState` now and after every event, connection change
+/// or output description that alters it.
+async fn stream_snapshots(
+    mut lines: Lines<BufReader<OwnedReadHalf>>,
+    mut write: OwnedWriteHalf,
+    client: &NiriClient,
+) -> anyhow::Result<()> {
+    let mut event_rx = client.event_tx.subscribe();
+    let mut connection_rx = client.connection_tx.subscribe();
+    let mut state_rx = client.state_tx.subscribe();
+    let mut last = Vec::new();
+    loop {
+        let mut snapshot = serde_json::to_vec(&*client.state.read().await)?;
+        snapshot.push(b'\n');
+        if snapshot != last {
+            write.write_all(&snapshot).await?;
+            last = snapshot;
+        }
+
+        tokio::select! {
+            received = event_rx.recv() => match received {
+                // A lagged receiver just means we skipped intermediate states;
+                // the next snapshot is complete either way.
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            changed = connection_rx.changed() => if changed.is_err() { return Ok(()) },
+            changed = state_rx.changed() => if changed.is_err() { return Ok(()) },
+            line = lines.next_line() => if !matches!(line, Ok(Some(_))) { return Ok(()) },
+        }
+    }
+}
+
+/// Forwards raw niri events as they arrive.
+async fn stream_events(
+    mut lines: Lines<BufReader<OwnedReadHalf>>,
+    mut write: OwnedWriteHalf,
+    client: &NiriClient,
+) -> anyhow::Result<()> {
+    let mut event_rx = client.event_tx.subscribe();
+    loop {
+        tokio::select! {
+            received = client.next_event(&mut event_rx) => match received {
+                Some(Received::Event(event)) => write_line(&mut write, &event).await?,
+                // Subscribers expect niri's own stream, so the snapshot goes
+                // out as the events niri opens a stream with.
+                Some(Received::Snapshot(state)) => {
+                    let windows = state.windows_by_workspace.into_values().flatten().collect();
+                    write_line(&mut write, &NiriEvent::WorkspacesChanged { workspaces: state.workspaces }).await?;
+                    write_line(&mut write, &NiriEvent::WindowsChanged { windows }).await?;
+                }
+                None => return Ok(()),
+            },
+            line = lines.next_line() => if !matches!(line, Ok(Some(_))) { return Ok(()) },
+        }
+    }
+}
+
+async fn dispatch(req: DaemonRequest, app: &AppHandle, client: &NiriClient) -> DaemonReply {
+    match req {
+        DaemonRequest::Show => Ok(DaemonResponse::OverlayVisible(crate::show_overlay(app))),
+        DaemonRequest::Hide => Ok(DaemonResponse::OverlayVisible(crate::hide_overlay(app))),
+        DaemonRequest::Toggle => Ok(DaemonResponse::OverlayVisible(crate::toggle_overlay(app))),
+        DaemonRequest::FocusWindow { id } => act_on_window(client, id, Action::FocusWindow { id }).await,
+        DaemonRequest::FocusPrevious => {
+            let previous = client.state.read().await.previous_window();
+            let id = previous.ok_or(CommandError::NoPreviousWindow)?;
+            act_on_window(client, id, Action::FocusWindow { id }).await
+        }
+        DaemonRequest::CycleNext => Ok(DaemonResponse::Selected(crate::cycle::step(app, client, 1).await)),
+        DaemonRequest::CyclePrev => Ok(DaemonResponse::Selected(crate::cycle::step(app, client, -1).await)),
+        DaemonRequest::Commit => crate::cycle::commit(app, client).await.map(DaemonResponse::Selected),
+        DaemonRequest::Cancel => {
+            crate::cycle::cancel(app);
+            Ok(DaemonResponse::Handled)
+        }
+        DaemonRequest::MoveWindowToWorkspace { window_id, workspace } => {
+            act_on_window(client, window_id, Action::MoveWindowToWorkspace {
+                window_id: Some(window_id),
+                reference: workspace,
+                focus: false,
+            }).await
+        }
+        DaemonRequest::FocusWorkspace { workspace } => {
+            act(client, Action::FocusWorkspace { reference: workspace }).await
+        }
+        DaemonRequest::MoveWindowToMonitor { window_id, output } => {
+            let output = client.state.read().await.resolve_output(&output)?;
+            let action = Action::MoveWindowToMonitor { id: Some(window_id), output };
+            act_on_window(client, window_id, action).await
+        }
+        DaemonRequest::MoveWorkspaceToMonitor { workspace, output } => {
+            let output = client.state.read().await.resolve_output(&output)?;
+            act(client, Action::MoveWorkspaceToMonitor { output, reference: Some(workspace) }).await
+        }
+        DaemonRequest::CloseWindow { id } => {
+            act_on_window(client, id, Action::CloseWindow { id: Some(id) }).await
+        }
+        DaemonRequest::State => Ok(DaemonResponse::State(Box::new(client.state.read().await.clone()))),
+        DaemonRequest::Status => {
+            let state = client.state.read().await;
+            Ok(DaemonResponse::Status(DaemonStatus {
+                pid: std::process::id(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                overlay_visible: crate::overlay_visible(app),
+                outputs: state.outputs.len(),
+                workspaces: state.workspaces.len(),
+                windows: state.total_windows(),
+                focused_workspace_id: state.focused_workspace_id,
+                focused_window_id: state.focused_window_id,
+                connection: state.connection.clone(),
+                lagged_events: client.lagged_events(),
+            }))
+        }
+        DaemonRequest::Reload => {
+            client.resync();
+            Ok(DaemonResponse::Handled)
+        }
+        DaemonRequest::Quit | DaemonRequest::Subscribe | DaemonRequest::SubscribeEvents => {
+            Err(CommandError::internal("request must be handled by the connection loop"))
+        }
+    }
+}
+
+async fn act(client: &NiriClient, action: Action) -> DaemonReply {
+    client.request(NiriRequest::Action(action)).await.map(|_| DaemonResponse::Handled)
+}
+
+async fn act_on_window(client: &NiriClient, id: u64, action: Action) -> DaemonReply {
+    client.act_on_window(id, action).await.map(|()| DaemonResponse::Handled)
+}
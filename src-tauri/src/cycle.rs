@@ -0,0 +1,174 @@
+//! Hold-modifier window cycling: `cycle-next` / `cycle-prev` / `commit` / `cancel`.
+//!
+//! The cursor lives in the daemon rather than the webview, so every
+//! `niri-switch cycle-next` spawned by a niri bind moves the same selection.
+//! The overlay only displays it, via the [`CYCLE_EVENT`] Tauri event.
+
+use std::sync::Mutex;
+
+use niri_ipc::Action;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::CommandError;
+use crate::ipc::NiriClient;
+
+/// Emitted to the overlay whenever the selection moves or the cycle ends.
+pub const CYCLE_EVENT: &str = "niri-switch://cycle";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CycleSelection {
+    /// The selected window, or `None` once the cycle is committed or cancelled.
+    pub window_id: Option<u64>,
+}
+
+/// Tauri-managed holder for the cycle in progress, if any.
+#[derive(Default)]
+pub struct Cycler(Mutex<Option<Cycle>>);
+
+/// One cycling session over a snapshot of the MRU list.
+#[derive(Debug)]
+struct Cycle {
+    windows: Vec<u64>,
+    /// `None` until the first step when no window was focused at the start.
+    cursor: Option<usize>,
+}
+
+impl Cycle {
+    fn new(mru: Vec<u64>, focused: Option<u64>, own: impl Fn(u64) -> bool) -> Self {
+        // The overlay's own window is never a target. While it has focus,
+        // the window under it counts as the focused one.
+        let windows: Vec<u64> = mru.into_iter().filter(|id| !own(*id)).collect();
+        let focused = match focused {
+            Some(id) if own(id) => windows.first().copied(),
+            focused => focused,
+        };
+        // Start on the focused window so the first `next` lands on the
+        // previous one, like every alt-tab.
+        let cursor = (focused.is_some() && windows.first() == focused.as_ref()).then_some(0);
+        Self { windows, cursor }
+    }
+
+    /// Moves the cursor by `delta`, wrapping, after dropping windows that
+    /// have closed since the cycle started.
+    fn step(&mut self, delta: isize, alive: impl Fn(u64) -> bool) -> Option<u64> {
+        let selected = self.selected();
+        self.windows.retain(|id| alive(*id));
+        if self.windows.is_empty() {
+            self.cursor = None;
+            return None;
+        }
+        let len = self.windows.len() as isize;
+        let next = match selected.and_then(|id| self.windows.iter().position(|w| *w == id)) {
+            Some(at) => (at as isize + delta).rem_euclid(len),
+            None if delta >= 0 => (delta - 1).rem_euclid(len),
+            None => delta.rem_euclid(len),
+        };
+        self.cursor = Some(next as usize);
+        self.selected()
+    }
+
+    fn selected(&self) -> Option<u64> {
+        self.cursor.and_then(|i| self.windows.get(i).copied())
+    }
+}
+
+/// Advances the selection by `delta`, starting a new cycle (and showing the
+/// overlay) unless one is already running on the visible overlay.
+pub async fn step(app: &AppHandle, client: &NiriClient, delta: isize) -> Option<u64> {
+    let selected = {
+        let state = client.state.read().await;
+        let cycler = app.state::<Cycler>();
+        let mut cycle = cycler.0.lock().unwrap();
+        if cycle.is_none() || !crate::overlay_visible(app) {
+            let own = |id| state.is_own_window(id);
+            *cycle = Some(Cycle::new(state.mru.clone(), state.focused_window_id, own));
+        }
+        cycle.as_mut().and_then(|c| c.step(delta, |id| state.mru.contains(&id)))
+    };
+    crate::show_overlay(app);
+    let _ = app.emit(CYCLE_EVENT, CycleSelection { window_id: selected });
+    selected
+}
+
+/// Ends the cycle, focusing the selected window and hiding the overlay.
+/// Returns the window that was focused, if any.
+pub async fn commit(app: &AppHandle, client: &NiriClient) -> Result<Option<u64>, CommandError> {
+    let selected = finish(app);
+    if let Some(id) = selected {
+        client.act_on_window(id, Action::FocusWindow { id }).await?;
+    }
+    Ok(selected)
+}
+
+/// Ends the cycle without changing focus and hides the overlay.
+pub fn cancel(app: &AppHandle) {
+    finish(app);
+}
+
+fn finish(app: &AppHandle) -> Option<u64> {
+    let cycle = app.state::<Cycler>().0.lock().unwrap().take();
+    // A cycle whose overlay was closed some other way is stale; don't act on it.
+    let selected = cycle.filter(|_| crate::overlay_visible(app)).and_then(|c| c.selected());
+    crate::hide_overlay(app);
+    let _ = app.emit(CYCLE_EVENT, CycleSelection { window_id: None });
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(cycle: &mut Cycle, delta: isize) -> Option<u64> {
+        cycle.step(delta, |_| true)
+    }
+
+    #[test]
+    fn next_starts_on_the_previous_window_and_wraps() {
+        let mut cycle = Cycle::new(vec![1, 2, 3], Some(1), |_| false);
+        assert_eq!(step(&mut cycle, 1), Some(2));
+        assert_eq!(step(&mut cycle, 1), Some(3));
+        assert_eq!(step(&mut cycle, 1), Some(1));
+        assert_eq!(step(&mut cycle, -1), Some(3));
+    }
+
+    #[test]
+    fn prev_starts_on_the_least_recent_window() {
+        let mut cycle = Cycle::new(vec![1, 2, 3], Some(1), |_| false);
+        assert_eq!(step(&mut cycle, -1), Some(3));
+    }
+
+    #[test]
+    fn without_focus_next_starts_on_the_most_recent_window() {
+        let mut cycle = Cycle::new(vec![1, 2, 3], None, |_| false);
+        assert_eq!(step(&mut cycle, 1), Some(1));
+
+        let mut cycle = Cycle::new(vec![1, 2, 3], None, |_| false);
+        assert_eq!(step(&mut cycle, -1), Some(3));
+    }
+
+    #[test]
+    fn the_overlay_is_never_selected() {
+        let overlay = |id| id == 9;
+        // Shown by the cycle itself: the overlay took focus after the snapshot.
+        let mut cycle = Cycle::new(vec![1, 2, 9, 3], Some(1), overlay);
+        assert_eq!(step(&mut cycle, 1), Some(2));
+        assert_eq!(step(&mut cycle, 1), Some(3));
+        assert_eq!(step(&mut cycle, 1), Some(1));
+
+        // Already open and focused: the window under it stands in for focus.
+        let mut cycle = Cycle::new(vec![1, 2, 3], Some(9), overlay);
+        assert_eq!(step(&mut cycle, 1), Some(2));
+        let mut cycle = Cycle::new(vec![9, 1, 2], Some(9), overlay);
+        assert_eq!(step(&mut cycle, -1), Some(2));
+    }
+
+    #[test]
+    fn closed_windows_are_skipped() {
+        let mut cycle = Cycle::new(vec![1, 2, 3, 4], Some(1), |_| false);
+        assert_eq!(step(&mut cycle, 1), Some(2));
+        assert_eq!(cycle.step(1, |id| id != 3), Some(4));
+        assert_eq!(cycle.step(1, |id| id != 2), Some(1));
+        assert_eq!(cycle.step(1, |_| false), None);
+    }
+}
@@ -0,0 +1,52 @@
+//! Errors returned by Tauri commands and control-socket requests.
+//!
+//! Serialized with a `kind` tag (`{"kind":"window_gone","id":42}`), so the
+//! overlay and the CLI can tell a window that just closed from a compositor
+//! that has gone away without parsing messages.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CommandError {
+    /// niri's socket is missing, refused us, or closed before replying.
+    #[error("niri is not reachable: {reason}")]
+    NiriUnavailable { reason: String },
+    /// niri answered the request with an error.
+    #[error("niri rejected the request: {reason}")]
+    NiriRejected { reason: String },
+    /// niri took longer than the request timeout to answer.
+    #[error("niri did not reply within {ms}ms")]
+    Timeout { ms: u64 },
+    /// The window closed before the request got to niri.
+    #[error("window {id} no longer exists")]
+    WindowGone { id: u64 },
+    #[error("unknown output `{name}`")]
+    UnknownOutput { name: String },
+    #[error("no previously focused window")]
+    NoPreviousWindow,
+    #[error("no icon found for `{app_id}`")]
+    IconNotFound { app_id: String },
+    /// A malformed request, or one this daemon can't carry out.
+    #[error("{reason}")]
+    Invalid { reason: String },
+    /// Anything else: I/O and (de)serialization failures.
+    #[error("{reason}")]
+    Internal { reason: String },
+}
+
+impl CommandError {
+    pub fn unavailable(e: impl std::fmt::Display) -> Self {
+        Self::NiriUnavailable { reason: e.to_string() }
+    }
+
+    pub fn internal(e: impl std::fmt::Display) -> Self {
+        Self::Internal { reason: e.to_string() }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(reason: String) -> Self {
+        Self::Invalid { reason }
+    }
+}
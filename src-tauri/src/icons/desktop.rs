@@ -0,0 +1,420 @@
+//! An index of the installed desktop entries, for finding the entry (and so
+//! the icon) behind a niri app id without touching the disk on each lookup.
+//! Built once at startup and rebuilt when an applications dir changes.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use inotify::WatchMask;
+use tracing::{debug, info, warn};
+
+use super::ini::{self, Ini};
+use crate::watch::DirWatch;
+
+/// How long an applications dir must be quiet before the index is rebuilt;
+/// a package install writes many entries at once.
+const SETTLE: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone)]
+pub struct DesktopEntry {
+    /// Desktop file ID without `.desktop`, e.g. `org.gnome.Nautilus`.
+    pub id: String,
+    pub path: PathBuf,
+    pub name: Option<String>,
+    pub icon: Option<String>,
+    pub no_display: bool,
+}
+
+/// How an app id matched an entry, strongest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchedBy {
+    Id,
+    StartupWmClass,
+    /// The basename of the program `Exec=` runs.
+    Exec,
+    /// `Name=` or one of its translations.
+    Name,
+}
+
+const MATCHES: [MatchedBy; 4] = [MatchedBy::Id, MatchedBy::StartupWmClass, MatchedBy::Exec, MatchedBy::Name];
+
+#[derive(Debug, Default)]
+pub struct DesktopIndex {
+    entries: Vec<DesktopEntry>,
+    /// Per [`MATCHES`] entry: lowercased key → index into `entries`.
+    keys: [HashMap<String, usize>; 4],
+}
+
+impl DesktopIndex {
+    /// Indexes `dirs`, highest priority first: an ID found in an earlier
+    /// dir shadows the same ID further down, and a `Hidden=true` entry
+    /// shadows it without being indexed itself.
+    pub fn build(dirs: &[PathBuf]) -> Self {
+        let mut index = Self::default();
+        let mut seen = HashSet::new();
+        for dir in dirs {
+            let mut files = Vec::new();
+            collect_entries(dir, dir, &mut files);
+            files.sort();
+            for (id, path) in files {
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                let Ok(text) = std::fs::read_to_string(&path) else { continue };
+                index.add(id, path, &Ini::parse(&text));
+            }
+        }
+        index
+    }
+
+    /// The entry for `app_id`, trying each way of matching in turn.
+    /// Comparisons ignore case; entries marked `NoDisplay` only win when
+    /// nothing visible matches the same way.
+    pub fn lookup(&self, app_id: &str) -> Option<(&DesktopEntry, MatchedBy)> {
+        let key = app_id.to_lowercase();
+        MATCHES.iter().zip(&self.keys)
+        .find_map(|(matched, keys)| keys.get(&key).map(|&i| (&self.entries[i], *matched)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn add(&mut self, id: String, path: PathBuf, ini: &Ini) {
+        let Some(group) = ini.group("Desktop Entry") else { return };
+        let flag = |key: &str| group.get(key).is_some_and(|v| v == "true");
+        if flag("Hidden") {
+            return;
+        }
+        let entry = DesktopEntry {
+            id,
+            path,
+            name: group.get("Name").cloned(),
+            icon: group.get("Icon").cloned().filter(|icon| !icon.is_empty()),
+            no_display: flag("NoDisplay"),
+        };
+
+        let names = group.iter()
+        .filter(|(key, _)| *key == "Name" || key.starts_with("Name["))
+        .map(|(_, name)| name.as_str());
+        let keys: [Vec<&str>; 4] = [
+            vec![entry.id.as_str()],
+            group.get("StartupWMClass").map(String::as_str).into_iter().collect(),
+            group.get("Exec").and_then(|exec| exec_program(exec)).into_iter().collect(),
+            names.collect(),
+        ];
+        let keys = keys.map(|keys| keys.into_iter().map(str::to_lowercase).collect::<Vec<_>>());
+
+        let i = self.entries.len();
+        let no_display = entry.no_display;
+        self.entries.push(entry);
+        for (map, keys) in self.keys.iter_mut().zip(keys) {
+            for key in keys {
+                let current = map.entry(key).or_insert(i);
+                // Earlier (higher priority) entries keep a key unless they
+                // are hidden from menus and this one isn't.
+                if self.entries[*current].no_display && !no_display {
+                    *current = i;
+                }
+            }
+        }
+    }
+}
+
+/// The program an `Exec=` line runs, without its directory: `code` for
+/// `/usr/bin/code --new-window %F`, `firefox` for `env A=1 firefox %u`.
+fn exec_program(exec: &str) -> Option<&str> {
+    let program = exec.split_whitespace()
+    .map(|token| token.trim_matches('"'))
+    .find(|token| *token != "env" && !token.contains('='))?;
+    Path::new(program).file_name()?.to_str()
+}
+
+/// Finds `*.desktop` files under `dir`, with their desktop file IDs
+/// (the path below `root`, `/` replaced by `-`, minus the extension).
+fn collect_entries(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            collect_entries(root, &path, out);
+            continue;
+        }
+        if path.extension().and_then(|e| e.to_str()) != Some("desktop") {
+            continue;
+        }
+        let stem = path.with_extension("");
+        let Some(relative) = stem.strip_prefix(root).ok().and_then(Path::to_str) else { continue };
+        out.push((relative.replace('/', "-"), path));
+    }
+}
+
+// ── Live index ────────────────────────────────────────────────────────────────
+
+pub struct DesktopEntries {
+    dirs: Vec<PathBuf>,
+    index: RwLock<Arc<DesktopIndex>>,
+}
+
+impl DesktopEntries {
+    /// `$XDG_DATA_HOME/applications`, then each `$XDG_DATA_DIRS/applications`.
+    pub fn from_env() -> Self {
+        let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".local/share"));
+        let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .unwrap_or_else(|_| "/usr/local/share:/usr/share".into());
+
+        let mut dirs = vec![data_home.join("applications")];
+        dirs.extend(ini::list(&data_dirs, ':').map(|dir| Path::new(dir).join("applications")));
+        Self::with_dirs(dirs)
+    }
+
+    pub fn with_dirs(dirs: Vec<PathBuf>) -> Self {
+        let index = DesktopIndex::build(&dirs);
+        info!("Indexed {} desktop entries", index.len());
+        Self { dirs, index: RwLock::new(Arc::new(index)) }
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    pub fn index(&self) -> Arc<DesktopIndex> {
+        Arc::clone(&self.index.read().unwrap())
+    }
+
+    pub fn refresh(&self) {
+        let index = DesktopIndex::build(&self.dirs);
+        debug!("Re-indexed {} desktop entries", index.len());
+        *self.index.write().unwrap() = Arc::new(index);
+    }
+
+    /// Rebuilds the index whenever an applications dir changes. Runs for
+    /// the life of the daemon unless no dir can be watched.
+    pub async fn watch(self: Arc<Self>) {
+        let mut watch = AppDirsWatch::new(&self.dirs);
+        loop {
+            if !watch.is_watching() {
+                warn!("Can't watch applications dirs, desktop entries won't refresh");
+                return;
+            }
+            let mut changed = watch.changed().await;
+            while changed.is_ok() {
+                match tokio::time::timeout(SETTLE, watch.changed()).await {
+                    Ok(more) => changed = more,
+                    Err(_settled) => break,
+                }
+            }
+            if let Err(e) = changed {
+                warn!("Watching applications dirs failed, desktop entries won't refresh: {e}");
+                return;
+            }
+            // Watch again before rebuilding, so dirs created since are
+            // covered and nothing written during the rebuild is missed.
+            watch = AppDirsWatch::new(&self.dirs);
+            let entries = Arc::clone(&self);
+            if let Err(e) = tokio::task::spawn_blocking(move || entries.refresh()).await {
+                warn!("Re-indexing desktop entries failed: {e}");
+            }
+        }
+    }
+}
+
+/// Watches on every applications dir and the dirs below it, and on the
+/// nearest existing parent of each applications dir that doesn't exist yet
+/// (a fresh `~/.local/share/applications`).
+struct AppDirsWatch {
+    dirs: Option<DirWatch>,
+    parents: Option<DirWatch>,
+    /// The names that, created in one of `parents`, bring a missing
+    /// applications dir one step closer.
+    awaited: HashSet<OsString>,
+}
+
+impl AppDirsWatch {
+    fn new(app_dirs: &[PathBuf]) -> Self {
+        let mut dirs = Vec::new();
+        let mut parents = Vec::new();
+        let mut awaited = HashSet::new();
+        for dir in app_dirs {
+            if dir.is_dir() {
+                dirs.push(dir.clone());
+                collect_subdirs(dir, &mut dirs);
+                continue;
+            }
+            let mut missing = dir.as_path();
+            while let Some(parent) = missing.parent() {
+                if parent.is_dir() {
+                    parents.push(parent.to_path_buf());
+                    awaited.extend(missing.file_name().map(OsStr::to_owned));
+                    break;
+                }
+                missing = parent;
+            }
+        }
+
+        let mask = WatchMask::CREATE
+        | WatchMask::DELETE
+        | WatchMask::CLOSE_WRITE
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO
+        | WatchMask::DELETE_SELF
+        | WatchMask::MOVE_SELF;
+        let watch = |dirs: &[PathBuf], mask| {
+            DirWatch::new(dirs.iter().map(PathBuf::as_path), mask)
+            .inspect_err(|e| debug!("Can't watch {dirs:?}: {e}"))
+            .ok()
+        };
+        Self {
+            dirs: watch(&dirs, mask),
+            parents: watch(&parents, WatchMask::CREATE | WatchMask::MOVED_TO),
+            awaited,
+        }
+    }
+
+    fn is_watching(&self) -> bool {
+        self.dirs.is_some() || self.parents.is_some()
+    }
+
+    /// Resolves on the next change to an applications dir, or on a missing
+    /// one (or a dir on the way to it) being created.
+    async fn changed(&mut self) -> std::io::Result<()> {
+        let Self { dirs, parents, awaited } = self;
+        loop {
+            tokio::select! {
+                names = next_change(dirs) => return names.map(drop),
+                names = next_change(parents) => {
+                    if names?.iter().any(|name| awaited.contains(name)) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn next_change(watch: &mut Option<DirWatch>) -> std::io::Result<Vec<OsString>> {
+    match watch {
+        Some(watch) => watch.changed().await,
+        None => std::future::pending().await,
+    }
+}
+
+fn collect_subdirs(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            out.push(entry.path());
+            collect_subdirs(&entry.path(), out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_entry(dir: &Path, file: &str, body: &str) {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, format!("[Desktop Entry]\nType=Application\n{body}")).unwrap();
+    }
+
+    fn temp_dirs(name: &str) -> (PathBuf, PathBuf) {
+        let base = std::env::temp_dir().join(format!("niri-switch-desktop-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let (user, system) = (base.join("user"), base.join("system"));
+        std::fs::create_dir_all(&user).unwrap();
+        std::fs::create_dir_all(&system).unwrap();
+        (user, system)
+    }
+
+    #[test]
+    fn matches_by_id_wm_class_exec_and_name_in_that_order() {
+        let (user, system) = temp_dirs("priority");
+        write_entry(&system, "code.desktop", "Name=Visual Studio Code\nExec=/usr/bin/code %F\nIcon=vscode\n");
+        write_entry(&system, "org.gnome.Nautilus.desktop", "Name=Files\nName[de]=Dateien\nIcon=nautilus\n");
+        write_entry(&system, "intellij.desktop", "Name=IDEA\nStartupWMClass=jetbrains-idea\nIcon=idea\n");
+        write_entry(&system, "wrapper.desktop", "Name=Wrapper\nExec=env GDK_BACKEND=x11 foot --app-id=x\nIcon=foot-x\n");
+        write_entry(&system, "kde4/kate.desktop", "Name=Kate\nIcon=kate\n");
+        write_entry(&system, "hidden.desktop", "Name=Hidden\nIcon=system\n");
+        write_entry(&user, "hidden.desktop", "Hidden=true\n");
+        let index = DesktopIndex::build(&[user.clone(), system]);
+        let lookup = |app_id| index.lookup(app_id).map(|(entry, matched)| (entry.icon.clone().unwrap(), matched));
+
+        assert_eq!(lookup("org.gnome.nautilus"), Some(("nautilus".into(), MatchedBy::Id)));
+        assert_eq!(lookup("code"), Some(("vscode".into(), MatchedBy::Id)));
+        assert_eq!(lookup("co"), None);
+        assert_eq!(lookup("Jetbrains-IDEA"), Some(("idea".into(), MatchedBy::StartupWmClass)));
+        assert_eq!(lookup("foot"), Some(("foot-x".into(), MatchedBy::Exec)));
+        assert_eq!(lookup("dateien"), Some(("nautilus".into(), MatchedBy::Name)));
+        assert_eq!(lookup("kde4-kate"), Some(("kate".into(), MatchedBy::Id)));
+        assert_eq!(lookup("hidden"), None);
+        let _ = std::fs::remove_dir_all(user.parent().unwrap());
+    }
+
+    #[test]
+    fn visible_entries_beat_no_display_ones() {
+        let (user, system) = temp_dirs("no-display");
+        write_entry(&user, "a-helper.desktop", "Name=Helper\nStartupWMClass=editor\nNoDisplay=true\nIcon=helper\n");
+        write_entry(&system, "z-editor.desktop", "Name=Editor\nStartupWMClass=editor\nIcon=editor\n");
+        let index = DesktopIndex::build(&[user.clone(), system]);
+
+        let (entry, matched) = index.lookup("editor").unwrap();
+        assert_eq!((entry.id.as_str(), matched), ("z-editor", MatchedBy::StartupWmClass));
+        let _ = std::fs::remove_dir_all(user.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn refreshes_when_an_entry_is_installed() {
+        let (user, system) = temp_dirs("watch");
+        let entries = Arc::new(DesktopEntries::with_dirs(vec![user.clone(), system]));
+        tokio::spawn(Arc::clone(&entries).watch());
+        // Let the watch start before installing anything.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        write_entry(&user, "new.app.desktop", "Name=New\nIcon=new-app\n");
+        let found = tokio::time::timeout(Duration::from_secs(5), async {
+            while entries.index().lookup("new.app").is_none() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }).await;
+        let _ = std::fs::remove_dir_all(user.parent().unwrap());
+        assert!(found.is_ok(), "the new entry was never indexed");
+    }
+
+    #[tokio::test]
+    async fn refreshes_for_new_subdirs_and_applications_dirs() {
+        let (user, system) = temp_dirs("watch-new-dirs");
+        std::fs::remove_dir_all(&user).unwrap();
+        let user_apps = user.join("share/applications");
+        let entries = Arc::new(DesktopEntries::with_dirs(vec![user_apps.clone(), system.clone()]));
+        tokio::spawn(Arc::clone(&entries).watch());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let indexed = |app_id: &'static str| {
+            let entries = Arc::clone(&entries);
+            tokio::time::timeout(Duration::from_secs(5), async move {
+                while entries.index().lookup(app_id).is_none() {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+        };
+
+        write_entry(&user_apps, "user.app.desktop", "Name=User\nIcon=user-app\n");
+        let user_found = indexed("user.app").await;
+        write_entry(&system, "vendor/first.desktop", "Name=First\nIcon=first\n");
+        let first_found = indexed("vendor-first").await;
+        write_entry(&system, "vendor/second.desktop", "Name=Second\nIcon=second\n");
+        let second_found = indexed("vendor-second").await;
+
+        let _ = std::fs::remove_dir_all(user.parent().unwrap());
+        assert!(user_found.is_ok(), "the new applications dir was never indexed");
+        assert!(first_found.is_ok(), "the new subdir was never indexed");
+        assert!(second_found.is_ok(), "changes in the new subdir weren't noticed");
+    }
+}
@@ -0,0 +1,50 @@
+//! The `[Group]` / `Key=Value` format shared by icon theme `index.theme`
+//! files, desktop entries and the GTK/KDE settings files.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct Ini {
+    groups: HashMap<String, HashMap<String, String>>,
+}
+
+impl Ini {
+    /// Lenient parse: comments (`#`, `;`), blank lines and anything that
+    /// isn't a group header or `key=value` pair are skipped. The first
+    /// occurrence of a group or key wins.
+    pub fn parse(text: &str) -> Self {
+        let mut groups: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut current = None;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                current = (!groups.contains_key(name)).then(|| name.to_string());
+                if let Some(name) = &current {
+                    groups.insert(name.clone(), HashMap::new());
+                }
+                continue;
+            }
+            let (Some(group), Some((key, value))) = (&current, line.split_once('=')) else { continue };
+            groups.get_mut(group).unwrap()
+            .entry(key.trim().to_string())
+            .or_insert_with(|| value.trim().to_string());
+        }
+        Self { groups }
+    }
+
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        self.groups.get(group)?.get(key).map(String::as_str)
+    }
+
+    pub fn group(&self, name: &str) -> Option<&HashMap<String, String>> {
+        self.groups.get(name)
+    }
+}
+
+/// Splits a list value (`a,b,c` in index.theme, `a;b;c;` in desktop entries).
+pub fn list(value: &str, separator: char) -> impl Iterator<Item = &str> {
+    value.split(separator).map(str::trim).filter(|item| !item.is_empty())
+}
@@ -0,0 +1,158 @@
+//! App icon resolution: maps a niri app id to an icon file, through the
+//! app's desktop entry and the installed XDG icon themes.
+
+mod desktop;
+mod ini;
+mod settings;
+mod theme;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::info;
+
+pub use desktop::{DesktopEntries, MatchedBy};
+pub use settings::ConfiguredTheme;
+pub use theme::IconThemes;
+
+/// The overlay draws app icons at 56px; ask for the next standard size up.
+pub const ICON_SIZE: u32 = 64;
+
+/// Searched, in order, after the user's own theme.
+const DEFAULT_THEMES: [&str; 5] = ["breeze", "Adwaita", "AdwaitaLegacy", "Papirus", "gnome"];
+
+pub struct IconResolver {
+    themes: IconThemes,
+    desktop: Arc<DesktopEntries>,
+    configured: Option<ConfiguredTheme>,
+    preferred: Vec<String>,
+}
+
+impl Default for IconResolver {
+    fn default() -> Self {
+        let configured = settings::configured_theme();
+        match &configured {
+            Some(theme) => info!("Using icon theme {} (from {})", theme.name, theme.source),
+            None => info!("No icon theme configured, trying {}", DEFAULT_THEMES.join(", ")),
+        }
+        Self::new(IconThemes::from_env(), DesktopEntries::from_env(), configured)
+    }
+}
+
+impl IconResolver {
+    pub fn new(themes: IconThemes, desktop: DesktopEntries, configured: Option<ConfiguredTheme>) -> Self {
+        let mut preferred: Vec<String> = configured.iter().map(|theme| theme.name.clone()).collect();
+        for theme in DEFAULT_THEMES {
+            if !preferred.iter().any(|t| t == theme) {
+                preferred.push(theme.to_string());
+            }
+        }
+        Self { themes, desktop: Arc::new(desktop), configured, preferred }
+    }
+
+    /// The icon for `app_id` at `size` logical pixels and integer `scale`.
+    pub fn find_icon_path(&self, app_id: &str, size: u32, scale: u32) -> Option<PathBuf> {
+        let lower = app_id.to_lowercase();
+        let last = lower.split('.').next_back().unwrap_or(&lower).to_string();
+        let candidates = [
+            lower.clone(),
+            last.clone(),
+            last.replace("-desktop", ""),
+            last.replace("-bin", ""),
+        ];
+
+        // The app's desktop entry says which icon it wants.
+        let desktop = self.desktop.index();
+        let entry = candidates.iter().find_map(|candidate| desktop.lookup(candidate));
+        if let Some(icon) = entry.and_then(|(entry, _)| entry.icon.as_deref()) {
+            // Desktop entries may name a file rather than a themed icon.
+            let path = Path::new(icon);
+            if path.is_absolute() {
+                if path.is_file() { return Some(path.to_path_buf()); }
+            } else if let Some(path) = self.themes.find(icon, &self.preferred, size, scale) {
+                return Some(path);
+            }
+        }
+        // No entry, or its icon isn't installed: guess from the app id.
+        candidates.iter().find_map(|candidate| self.themes.find(candidate, &self.preferred, size, scale))
+    }
+
+    pub fn themes(&self) -> &IconThemes {
+        &self.themes
+    }
+
+    /// The desktop entry index, shared with the task that keeps it fresh.
+    pub fn desktop_entries(&self) -> Arc<DesktopEntries> {
+        Arc::clone(&self.desktop)
+    }
+
+    /// The user's theme, if one could be found.
+    pub fn configured(&self) -> Option<&ConfiguredTheme> {
+        self.configured.as_ref()
+    }
+
+    /// Themes searched in order, the user's first, each with its parents
+    /// and then `hicolor`.
+    pub fn preferred(&self) -> &[String] {
+        &self.preferred
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install_theme(base: &Path, theme: &str, icons: &[&str]) {
+        std::fs::create_dir_all(base.join(theme).join("64x64/apps")).unwrap();
+        std::fs::write(
+            base.join(theme).join("index.theme"),
+            "[Icon Theme]\nDirectories=64x64/apps\n\n[64x64/apps]\nSize=64\n",
+        ).unwrap();
+        for icon in icons {
+            std::fs::write(base.join(theme).join(format!("64x64/apps/{icon}.svg")), "").unwrap();
+        }
+    }
+
+    #[test]
+    fn the_configured_theme_is_searched_first() {
+        let base = std::env::temp_dir().join(format!("niri-switch-resolver-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        for theme in ["breeze", "Papirus"] {
+            install_theme(&base, theme, &["firefox"]);
+        }
+        let resolver = |configured: Option<&str>| IconResolver::new(
+            IconThemes::with_base_dirs(vec![base.clone()]),
+            DesktopEntries::with_dirs(Vec::new()),
+            configured.map(|name| ConfiguredTheme { name: name.into(), source: "test".into() }),
+        );
+
+        let found = resolver(Some("Papirus")).find_icon_path("org.mozilla.firefox", 64, 1);
+        assert_eq!(found, Some(base.join("Papirus/64x64/apps/firefox.svg")));
+        let found = resolver(None).find_icon_path("org.mozilla.firefox", 64, 1);
+        assert_eq!(found, Some(base.join("breeze/64x64/apps/firefox.svg")));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn the_desktop_entry_icon_beats_guessing_from_the_app_id() {
+        let base = std::env::temp_dir().join(format!("niri-switch-resolver-entry-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        install_theme(&base, "breeze", &["code", "vscode", "kitty"]);
+        let apps = base.join("applications");
+        std::fs::create_dir_all(&apps).unwrap();
+        std::fs::write(apps.join("code.desktop"), "[Desktop Entry]\nName=Code\nIcon=vscode\n").unwrap();
+        std::fs::write(apps.join("kitty.desktop"), "[Desktop Entry]\nName=kitty\nIcon=not-installed\n").unwrap();
+        let resolver = IconResolver::new(
+            IconThemes::with_base_dirs(vec![base.clone()]),
+            DesktopEntries::with_dirs(vec![apps]),
+            None,
+        );
+
+        let found = resolver.find_icon_path("code", 64, 1);
+        assert_eq!(found, Some(base.join("breeze/64x64/apps/vscode.svg")));
+        // An entry whose icon is missing still leaves the app id to go on.
+        let found = resolver.find_icon_path("kitty", 64, 1);
+        assert_eq!(found, Some(base.join("breeze/64x64/apps/kitty.svg")));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,148 @@
+//! Which icon theme the user picked. niri has no setting of its own, so this
+//! asks the places GTK and KDE apps read theirs from.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use super::ini::Ini;
+
+/// Overrides every desktop setting.
+pub const THEME_ENV: &str = "NIRI_SWITCH_ICON_THEME";
+
+/// `org.gnome.desktop.interface icon-theme` when nobody has set it.
+const GNOME_DEFAULT: &str = "Adwaita";
+
+/// How long gsettings gets to answer. Without a session bus it can hang
+/// waiting for dconf, and the daemon asks while starting up.
+const GSETTINGS_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfiguredTheme {
+    pub name: String,
+    /// Where the name came from, for logs and `debug_icon_search`.
+    pub source: String,
+}
+
+/// The first theme set in, in order: `$NIRI_SWITCH_ICON_THEME`, GNOME's
+/// `icon-theme` setting, GTK 3 and GTK 4 `settings.ini`, and `kdeglobals`.
+pub fn configured_theme() -> Option<ConfiguredTheme> {
+    let found = |name: String, source: &str| ConfiguredTheme { name, source: source.to_string() };
+    if let Some(name) = std::env::var(THEME_ENV).ok().filter(|name| !name.trim().is_empty()) {
+        return Some(found(name.trim().to_string(), THEME_ENV));
+    }
+    // gsettings can't tell a chosen theme from the schema's default, which
+    // it reports on machines that never ran GNOME; a theme named in a
+    // settings file is the likelier choice there.
+    let gsettings = gsettings_theme();
+    match gsettings {
+        Some(name) if name != GNOME_DEFAULT => Some(found(name, "gsettings")),
+        _ => theme_from_config_files(&config_home()).or_else(|| Some(found(gsettings?, "gsettings"))),
+    }
+}
+
+/// The GTK and KDE settings files under `config_home`.
+pub fn theme_from_config_files(config_home: &Path) -> Option<ConfiguredTheme> {
+    let files = [
+        ("gtk-3.0/settings.ini", "Settings", "gtk-icon-theme-name"),
+        ("gtk-4.0/settings.ini", "Settings", "gtk-icon-theme-name"),
+        ("kdeglobals", "Icons", "Theme"),
+    ];
+    files.into_iter().find_map(|(file, group, key)| {
+        let path = config_home.join(file);
+        let ini = Ini::parse(&std::fs::read_to_string(&path).ok()?);
+        let name = ini.get(group, key).map(unquote).filter(|name| !name.is_empty())?;
+        Some(ConfiguredTheme { name: name.to_string(), source: path.display().to_string() })
+    })
+}
+
+fn gsettings_theme() -> Option<String> {
+    let mut gsettings = Command::new("gsettings");
+    gsettings.args(["get", "org.gnome.desktop.interface", "icon-theme"]);
+    let stdout = stdout_within(gsettings, GSETTINGS_TIMEOUT)?;
+    let name = unquote(stdout.trim()).to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+/// What `command` prints, if it succeeds within `timeout`; it is killed
+/// otherwise. Only for commands with little output, which is read once
+/// they have exited.
+fn stdout_within(mut command: Command, timeout: Duration) -> Option<String> {
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().ok()?;
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait().ok()? {
+            Some(status) => break status,
+            None if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+            None => {
+                warn!("{:?} didn't answer within {timeout:?}, skipping it", command.get_program());
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+    let mut stdout = Vec::new();
+    child.stdout.take()?.read_to_end(&mut stdout).ok()?;
+    status.success().then(|| String::from_utf8_lossy(&stdout).into_owned())
+}
+
+/// gsettings prints GVariant strings (`'Papirus-Dark'`); settings.ini
+/// values are sometimes written with double quotes.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['\'', '"'].into_iter()
+    .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+    .unwrap_or(value)
+}
+
+fn config_home() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+    .map(PathBuf::from)
+    .unwrap_or_else(|| PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gtk_settings_win_over_kdeglobals() {
+        let home = std::env::temp_dir().join(format!("niri-switch-icon-settings-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(home.join("gtk-4.0")).unwrap();
+        std::fs::write(home.join("kdeglobals"), "[General]\nTheme=nope\n\n[Icons]\nTheme=breeze-dark\n").unwrap();
+        let theme = |home: &Path| theme_from_config_files(home).map(|t| t.name);
+
+        assert_eq!(theme(&home).as_deref(), Some("breeze-dark"));
+        std::fs::write(home.join("gtk-4.0/settings.ini"), "[Settings]\ngtk-icon-theme-name = \"Papirus-Dark\"\n").unwrap();
+        assert_eq!(theme(&home).as_deref(), Some("Papirus-Dark"));
+
+        let _ = std::fs::remove_dir_all(&home);
+        assert_eq!(theme(&home), None);
+    }
+
+    #[test]
+    fn commands_that_hang_are_given_up_on() {
+        let run = |script: &str| {
+            let mut sh = Command::new("sh");
+            sh.args(["-c", script]);
+            stdout_within(sh, Duration::from_millis(200))
+        };
+        assert_eq!(run("echo \"'Papirus'\"").as_deref(), Some("'Papirus'\n"));
+        assert_eq!(run("echo nope; exit 1"), None);
+
+        let started = Instant::now();
+        assert_eq!(run("sleep 10"), None);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn unquotes_gvariant_strings() {
+        assert_eq!(unquote("'Papirus-Dark'"), "Papirus-Dark");
+        assert_eq!(unquote("Adwaita"), "Adwaita");
+    }
+}
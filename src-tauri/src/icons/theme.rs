@@ -0,0 +1,342 @@
+//! Icon lookup as the freedesktop Icon Theme spec describes it: themes are
+//! read from their `index.theme`, searched down their `Inherits=` chain to
+//! `hicolor`, and within a theme the directory whose `Size`/`Scale`/`Type`
+//! fits the requested size best wins. Unthemed icons (`/usr/share/pixmaps`)
+//! are the last resort.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tracing::debug;
+
+use super::ini::{self, Ini};
+
+/// Extensions the spec allows, in the order it prefers them.
+const EXTENSIONS: [&str; 3] = ["png", "svg", "xpm"];
+
+pub struct IconThemes {
+    /// `$HOME/.icons`, `$XDG_DATA_HOME/icons`, `$XDG_DATA_DIRS/icons` and
+    /// `/usr/share/pixmaps`, in the spec's order.
+    base_dirs: Vec<PathBuf>,
+    /// Themes by name as they are first needed; `None` for ones not installed.
+    loaded: Mutex<HashMap<String, Option<Arc<Theme>>>>,
+}
+
+impl IconThemes {
+    pub fn from_env() -> Self {
+        let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".local/share"));
+        let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .unwrap_or_else(|_| "/usr/local/share:/usr/share".into());
+
+        let mut base_dirs = vec![home.join(".icons"), data_home.join("icons")];
+        base_dirs.extend(ini::list(&data_dirs, ':').map(|dir| Path::new(dir).join("icons")));
+        base_dirs.push(PathBuf::from("/usr/share/pixmaps"));
+        Self::with_base_dirs(base_dirs)
+    }
+
+    pub fn with_base_dirs(base_dirs: Vec<PathBuf>) -> Self {
+        Self { base_dirs, loaded: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn base_dirs(&self) -> &[PathBuf] {
+        &self.base_dirs
+    }
+
+    /// Finds `icon` for display at `size` logical pixels on a `scale`x
+    /// output, trying `themes` in order, then `hicolor`, then unthemed icons.
+    pub fn find(&self, icon: &str, themes: &[String], size: u32, scale: u32) -> Option<PathBuf> {
+        let mut visited = HashSet::new();
+        themes.iter().map(String::as_str).chain(["hicolor"])
+        .find_map(|theme| self.find_in(theme, icon, size, scale, &mut visited))
+        .or_else(|| self.find_unthemed(icon))
+    }
+
+    /// `theme`'s chain of themes, parents after children, for diagnostics.
+    pub fn chain(&self, theme: &str) -> Vec<String> {
+        let mut chain = Vec::new();
+        let mut pending = vec![theme.to_string()];
+        while let Some(name) = pending.pop() {
+            if chain.contains(&name) {
+                continue;
+            }
+            if let Some(theme) = self.theme(&name) {
+                pending.extend(theme.parents().rev().map(str::to_string));
+            }
+            chain.push(name);
+        }
+        chain
+    }
+
+    fn find_in(
+        &self,
+        name: &str,
+        icon: &str,
+        size: u32,
+        scale: u32,
+        visited: &mut HashSet<String>,
+    ) -> Option<PathBuf> {
+        if !visited.insert(name.to_string()) {
+            return None;
+        }
+        let theme = self.theme(name)?;
+        if let Some(path) = theme.lookup(icon, size, scale) {
+            return Some(path.to_path_buf());
+        }
+        for parent in theme.parents() {
+            if let Some(path) = self.find_in(parent, icon, size, scale, visited) {
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    fn find_unthemed(&self, icon: &str) -> Option<PathBuf> {
+        self.base_dirs.iter()
+        .flat_map(|dir| EXTENSIONS.map(|ext| dir.join(format!("{icon}.{ext}"))))
+        .find(|path| path.is_file())
+    }
+
+    fn theme(&self, name: &str) -> Option<Arc<Theme>> {
+        let mut loaded = self.loaded.lock().unwrap();
+        loaded.entry(name.to_string())
+        .or_insert_with(|| {
+            let theme = Theme::load(name, &self.base_dirs);
+            if theme.is_none() {
+                debug!("icon theme {name} is not installed");
+            }
+            theme.map(Arc::new)
+        })
+        .clone()
+    }
+}
+
+// ── Themes ────────────────────────────────────────────────────────────────────
+
+struct Theme {
+    name: String,
+    inherits: Vec<String>,
+    dirs: Vec<ThemeDir>,
+    /// Every icon file in the theme by icon name, in lookup order: by
+    /// directory as `index.theme` lists them, then base directory, then
+    /// extension.
+    icons: HashMap<String, Vec<(usize, PathBuf)>>,
+}
+
+impl Theme {
+    fn load(name: &str, base_dirs: &[PathBuf]) -> Option<Self> {
+        // The first index.theme found describes the theme; its directories
+        // are looked for under every base directory.
+        let roots: Vec<PathBuf> = base_dirs.iter().map(|dir| dir.join(name)).filter(|root| root.is_dir()).collect();
+        let index = roots.iter().find_map(|root| std::fs::read_to_string(root.join("index.theme")).ok())?;
+        let index = Ini::parse(&index);
+
+        let listed = ["Directories", "ScaledDirectories"].into_iter()
+        .filter_map(|key| index.get("Icon Theme", key))
+        .flat_map(|value| ini::list(value, ','));
+        let mut seen = HashSet::new();
+        let dirs: Vec<ThemeDir> = listed
+        .filter(|dir| seen.insert(*dir))
+        .filter_map(|dir| ThemeDir::parse(dir, &index))
+        .collect();
+
+        let mut icons: HashMap<String, Vec<(usize, PathBuf)>> = HashMap::new();
+        for (dir_idx, dir) in dirs.iter().enumerate() {
+            for root in &roots {
+                let Ok(entries) = std::fs::read_dir(root.join(&dir.path)) else { continue };
+                let mut found: Vec<(usize, String, PathBuf)> = entries.flatten()
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    let ext = path.extension()?.to_str()?;
+                    let rank = EXTENSIONS.iter().position(|e| *e == ext)?;
+                    Some((rank, path.file_stem()?.to_str()?.to_string(), path))
+                })
+                .collect();
+                found.sort_by_key(|(rank, ..)| *rank);
+                for (_, icon, path) in found {
+                    icons.entry(icon).or_default().push((dir_idx, path));
+                }
+            }
+        }
+
+        let inherits = index.get("Icon Theme", "Inherits")
+        .map(|value| ini::list(value, ',').map(str::to_string).collect())
+        .unwrap_or_default();
+        Some(Self { name: name.to_string(), inherits, dirs, icons })
+    }
+
+    /// Parents to search next. A theme that names none still falls back
+    /// to `hicolor`, as every theme must.
+    fn parents(&self) -> impl DoubleEndedIterator<Item = &str> {
+        let hicolor = (self.inherits.is_empty() && self.name != "hicolor").then_some("hicolor");
+        self.inherits.iter().map(String::as_str).chain(hicolor)
+    }
+
+    /// An exact size match if there is one, else the closest size.
+    fn lookup(&self, icon: &str, size: u32, scale: u32) -> Option<&Path> {
+        let candidates = self.icons.get(icon)?;
+        candidates.iter()
+        .find(|(dir, _)| self.dirs[*dir].matches(size, scale))
+        .or_else(|| candidates.iter().min_by_key(|(dir, _)| self.dirs[*dir].distance(size, scale)))
+        .map(|(_, path)| path.as_path())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+#[derive(Debug)]
+struct ThemeDir {
+    path: String,
+    size: u32,
+    scale: u32,
+    kind: DirType,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+impl ThemeDir {
+    /// Reads the directory's own group; one without a `Size` is ignored.
+    fn parse(path: &str, index: &Ini) -> Option<Self> {
+        let group = index.group(path)?;
+        let number = |key: &str| group.get(key).and_then(|v| v.parse::<u32>().ok());
+        let size = number("Size")?;
+        let kind = match group.get("Type").map(String::as_str) {
+            Some("Fixed")    => DirType::Fixed,
+            Some("Scalable") => DirType::Scalable,
+            _                => DirType::Threshold,
+        };
+        Some(Self {
+            path: path.to_string(),
+            size,
+            scale: number("Scale").unwrap_or(1).max(1),
+            kind,
+            min_size: number("MinSize").unwrap_or(size),
+            max_size: number("MaxSize").unwrap_or(size),
+            threshold: number("Threshold").unwrap_or(2),
+        })
+    }
+
+    /// The (min, max) icon sizes, in logical pixels, this directory serves.
+    fn range(&self) -> (u32, u32) {
+        match self.kind {
+            DirType::Fixed     => (self.size, self.size),
+            DirType::Scalable  => (self.min_size, self.max_size),
+            DirType::Threshold => (self.size.saturating_sub(self.threshold), self.size + self.threshold),
+        }
+    }
+
+    fn matches(&self, size: u32, scale: u32) -> bool {
+        let (min, max) = self.range();
+        self.scale == scale && (min..=max).contains(&size)
+    }
+
+    /// How far off, in device pixels, this directory's icons are.
+    fn distance(&self, size: u32, scale: u32) -> u32 {
+        let (min, max) = self.range();
+        let wanted = size * scale;
+        (min * self.scale).saturating_sub(wanted) + wanted.saturating_sub(max * self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds icon themes under a fresh temp dir from `(theme, index.theme,
+    /// icon files)` and returns it as the only base directory.
+    fn themes(name: &str, themes: &[(&str, &str, &[&str])]) -> (IconThemes, PathBuf) {
+        let base = std::env::temp_dir().join(format!("niri-switch-icons-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        for (theme, index, files) in themes {
+            let root = base.join(theme);
+            std::fs::create_dir_all(&root).unwrap();
+            std::fs::write(root.join("index.theme"), index).unwrap();
+            for file in *files {
+                let path = root.join(file);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, "").unwrap();
+            }
+        }
+        (IconThemes::with_base_dirs(vec![base.clone()]), base)
+    }
+
+    const HICOLOR: &str = "[Icon Theme]
+Name=Hicolor
+Directories=48x48/apps,scalable/apps
+
+[48x48/apps]
+Size=48
+Type=Threshold
+
+[scalable/apps]
+Size=128
+MinSize=8
+MaxSize=512
+Type=Scalable
+";
+
+    #[test]
+    fn picks_the_best_size_at_the_requested_scale() {
+        let index = "[Icon Theme]
+Directories=32x32/apps,64x64/apps
+ScaledDirectories=32x32@2/apps
+
+[32x32/apps]
+Size=32
+Type=Fixed
+
+[64x64/apps]
+Size=64
+Type=Fixed
+
+[32x32@2/apps]
+Size=32
+Scale=2
+Type=Fixed
+";
+        let (themes, base) = themes("sizes", &[(
+            "Sized", index,
+            &["32x32/apps/term.png", "64x64/apps/term.png", "32x32@2/apps/term.png"],
+        )]);
+        let sized = ["Sized".to_string()];
+
+        let at = |size, scale| themes.find("term", &sized, size, scale).unwrap();
+        assert_eq!(at(64, 1), base.join("Sized/64x64/apps/term.png"));
+        assert_eq!(at(32, 2), base.join("Sized/32x32@2/apps/term.png"));
+        // No exact match: 56px is closest to the 64px icons.
+        assert_eq!(at(56, 1), base.join("Sized/64x64/apps/term.png"));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn follows_inherits_down_to_hicolor_then_pixmaps() {
+        let dark = "[Icon Theme]\nInherits=Papirus,breeze\nDirectories=64x64/apps\n\n[64x64/apps]\nSize=64\n";
+        let papirus = "[Icon Theme]\nDirectories=64x64/apps\n\n[64x64/apps]\nSize=64\n";
+        let (themes, base) = themes("inherits", &[
+            ("Papirus-Dark", dark, &["64x64/apps/own.svg"]),
+            ("Papirus", papirus, &["64x64/apps/firefox.svg"]),
+            ("hicolor", HICOLOR, &["scalable/apps/steam.svg", "48x48/apps/steam.png"]),
+        ]);
+        std::fs::write(base.join("xterm.xpm"), "").unwrap();
+        let dark_first = ["Papirus-Dark".to_string()];
+
+        let find = |icon| themes.find(icon, &dark_first, 64, 1);
+        assert_eq!(find("own"), Some(base.join("Papirus-Dark/64x64/apps/own.svg")));
+        assert_eq!(find("firefox"), Some(base.join("Papirus/64x64/apps/firefox.svg")));
+        // breeze isn't installed; Papirus falls back to hicolor on its own.
+        assert_eq!(find("steam"), Some(base.join("hicolor/scalable/apps/steam.svg")));
+        assert_eq!(find("xterm"), Some(base.join("xterm.xpm")));
+        assert_eq!(find("missing"), None);
+        assert_eq!(themes.chain("Papirus-Dark"), ["Papirus-Dark", "Papirus", "hicolor", "breeze"]);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,60 @@
+//! Delays between reconnect attempts: exponential, capped, and jittered so a
+//! crowd of clients doesn't retry in lockstep.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+const INITIAL: Duration = Duration::from_millis(500);
+const MAX: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub struct Backoff {
+    next: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self { next: INITIAL }
+    }
+}
+
+impl Backoff {
+    /// Starts over from the shortest delay, after a connection that worked.
+    pub fn reset(&mut self) {
+        self.next = INITIAL;
+    }
+
+    /// The delay before the next attempt: twice the previous one up to
+    /// [`MAX`], give or take 20%.
+    pub fn next_delay(&mut self) -> Duration {
+        let base = self.next;
+        self.next = (self.next * 2).min(MAX);
+        base.mul_f64(0.8 + 0.4 * random_unit())
+    }
+}
+
+/// A number in `[0, 1)`. `RandomState` is seeded randomly per instance,
+/// which is plenty for jitter and spares us an RNG dependency.
+fn random_unit() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_double_up_to_the_cap_and_reset() {
+        let mut backoff = Backoff::default();
+        let within = |d: Duration, base: Duration| d >= base.mul_f64(0.8) && d <= base.mul_f64(1.2);
+
+        for base in [500, 1000, 2000, 4000, 8000, 16000, 32000, 60000, 60000] {
+            let delay = backoff.next_delay();
+            assert!(within(delay, Duration::from_millis(base)), "{delay:?} vs {base}ms");
+        }
+        backoff.reset();
+        assert!(within(backoff.next_delay(), INITIAL));
+    }
+}
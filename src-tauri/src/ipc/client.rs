@@ -1,16 +1,57 @@
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use inotify::WatchMask;
+use niri_ipc::Action;
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    net::UnixStream,
-    sync::{broadcast, RwLock},
+    net::{unix::OwnedWriteHalf, UnixStream},
+    sync::{broadcast, broadcast::error::RecvError, oneshot, watch, Notify, RwLock},
+    task::JoinHandle,
 };
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
-use super::types::{socket_path, NiriEvent, NiriRequest, NiriResponse, NiriState};
+use super::backoff::Backoff;
+use super::recording::{self, Recorder};
+use super::reducer::{reduce, set_outputs};
+use super::types::{
+    is_known_event, is_niri_socket_name, socket_path, Compatibility, ConnectionStatus, NiriEvent, NiriRequest, NiriResponse,
+    NiriState, Output, NIRI_IPC_VERSION,
+};
+use crate::error::CommandError;
+use crate::watch::DirWatch;
+
+/// How long a request may take, connecting included, before it fails.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct NiriClient {
     pub state: Arc<RwLock<NiriState>>,
     pub event_tx: broadcast::Sender<NiriEvent>,
+    /// Mirrors `state.connection`, for whoever needs to know when it changes.
+    pub connection_tx: watch::Sender<ConnectionStatus>,
+    /// Pinged when the state changes with no event to show for it: when
+    /// niri's description of its outputs comes in.
+    pub state_tx: watch::Sender<()>,
+    resync: Notify,
+    has_connected: AtomicBool,
+    refreshing_outputs: AtomicBool,
+    recorder: Option<Mutex<Recorder>>,
+    /// Shared by every `request`; opened on first use and after niri drops it.
+    requests: tokio::sync::Mutex<Option<RequestConn>>,
+    request_timeout: Duration,
+    lagged_events: AtomicU64,
+    /// Event kinds from a newer niri that have been warned about.
+    unknown_events: Mutex<HashSet<String>>,
+}
+
+/// What [`NiriClient::next_event`] hands a subscriber.
+pub enum Received {
+    Event(NiriEvent),
+    /// Stands in for events the subscriber missed.
+    Snapshot(NiriState),
 }
 
 impl NiriClient {
@@ -19,35 +60,148 @@ impl NiriClient {
         let client = Self {
             state: Arc::new(RwLock::new(NiriState::default())),
             event_tx: tx,
+            connection_tx: watch::Sender::new(ConnectionStatus::default()),
+            state_tx: watch::Sender::new(()),
+            resync: Notify::new(),
+            has_connected: AtomicBool::new(false),
+            refreshing_outputs: AtomicBool::new(false),
+            recorder: None,
+            requests: tokio::sync::Mutex::new(None),
+            request_timeout: REQUEST_TIMEOUT,
+            lagged_events: AtomicU64::new(0),
+            unknown_events: Mutex::new(HashSet::new()),
         };
         (client, rx)
     }
 
-    pub async fn request(&self, req: NiriRequest) -> anyhow::Result<NiriResponse> {
-        let path = socket_path()?;
-        let mut stream = UnixStream::connect(&path).await?;
-        let payload = serde_json::to_vec(&req)?;
-        stream.write_all(&payload).await?;
-        stream.write_all(b"\n").await?;
-        stream.shutdown().await?;
-        let mut reader = BufReader::new(&mut stream);
-        let mut line = String::new();
-        reader.read_line(&mut line).await?;
-        let reply: niri_ipc::Reply = serde_json::from_str(line.trim())?;
-        reply.map_err(|e| anyhow::anyhow!("Niri error: {e}"))
+    /// Writes every raw event line from the live stream to `path` (see
+    /// `recording` for the format). Call before sharing the client.
+    pub fn record_to(&mut self, path: &Path) -> std::io::Result<()> {
+        self.recorder = Some(Mutex::new(Recorder::create(path)?));
+        info!("Recording Niri events to {}", path.display());
+        Ok(())
+    }
+
+    /// Sends `req` over the shared request connection and waits for niri's
+    /// reply. Concurrent calls are pipelined rather than serialised.
+    pub async fn request(&self, req: NiriRequest) -> Result<NiriResponse, CommandError> {
+        let mut payload = serde_json::to_vec(&req).map_err(CommandError::internal)?;
+        payload.push(b'\n');
+        let deadline = tokio::time::Instant::now() + self.request_timeout;
+        let timed_out = || CommandError::Timeout { ms: self.request_timeout.as_millis() as u64 };
+
+        let reply_rx = match tokio::time::timeout_at(deadline, self.send_request(&payload)).await {
+            Ok(reply_rx) => reply_rx?,
+            Err(_) => {
+                // The write may have been cut off mid-line: start over on a
+                // new connection.
+                self.requests.lock().await.take();
+                return Err(timed_out());
+            }
+        };
+        // Only this caller gives up. Its slot stays queued, so a late reply
+        // is discarded and the others on the connection still get theirs.
+        let reply = tokio::time::timeout_at(deadline, reply_rx).await.map_err(|_| timed_out())?
+        .map_err(|_| CommandError::unavailable("niri closed the connection before replying"))?
+        .map_err(|e| CommandError::internal(format!("malformed reply from niri: {e}")))?;
+        reply.map_err(|reason| CommandError::NiriRejected { reason })
+    }
+
+    /// Sends an action aimed at window `id`, failing with
+    /// [`CommandError::WindowGone`] instead if that window has already closed;
+    /// niri itself quietly ignores actions on unknown windows.
+    pub async fn act_on_window(&self, id: u64, action: Action) -> Result<(), CommandError> {
+        if !self.state.read().await.has_window(id) {
+            return Err(CommandError::WindowGone { id });
+        }
+        self.request(NiriRequest::Action(action)).await.map(|_| ())
+    }
+
+    /// Writes the request, reconnecting if needed, and hands back where its
+    /// reply will arrive.
+    async fn send_request(&self, payload: &[u8]) -> Result<ReplyRx, CommandError> {
+        let mut conn = self.requests.lock().await;
+        let sent = match conn.as_mut() {
+            Some(conn) => conn.send(payload).await,
+            None => None,
+        };
+        match sent {
+            Some(rx) => Ok(rx),
+            // Never opened, or closed by niri since: one fresh attempt.
+            None => {
+                let path = socket_path().map_err(CommandError::unavailable)?;
+                let fresh = RequestConn::connect(&path).await.map_err(CommandError::unavailable)?;
+                conn.insert(fresh).send(payload).await
+                .ok_or_else(|| CommandError::unavailable("niri closed the connection"))
+            }
+        }
+    }
+
+    /// Drops the current event stream so `run_event_stream` reconnects at once.
+    /// Niri replays the full workspace/window state on every new stream.
+    pub fn resync(&self) {
+        self.resync.notify_one();
+    }
+
+    /// Receives the next event for a subscriber to `event_tx`. One that has
+    /// fallen behind and lost events gets the full current state in their
+    /// place, so it can carry on instead of drifting. `None` once the client
+    /// is gone.
+    pub async fn next_event(&self, rx: &mut broadcast::Receiver<NiriEvent>) -> Option<Received> {
+        match rx.recv().await {
+            Ok(event) => Some(Received::Event(event)),
+            Err(RecvError::Lagged(n)) => {
+                let total = self.lagged_events.fetch_add(n, Ordering::Relaxed) + n;
+                warn!("Event subscriber lagged by {n} events ({total} in total) — resending full state");
+                Some(Received::Snapshot(self.state.read().await.clone()))
+            }
+            Err(RecvError::Closed) => None,
+        }
+    }
+
+    /// Events dropped by subscribers that fell behind, since startup.
+    pub fn lagged_events(&self) -> u64 {
+        self.lagged_events.load(Ordering::Relaxed)
     }
 
     pub async fn run_event_stream(self: Arc<Self>) {
+        let mut backoff = Backoff::default();
         loop {
-            match self.connect_event_stream().await {
-                Ok(()) => warn!("Niri event stream ended — reconnecting…"),
-                Err(e) => error!("Event stream error: {e} — reconnecting in 2s"),
+            // Watch before connecting: a socket created while the attempt
+            // fails would otherwise go unnoticed until the timer runs out.
+            let mut socket = SocketWatch::new();
+            tokio::select! {
+                result = self.connect_event_stream() => {
+                    // A stream that got going earns a quick retry.
+                    if self.connection_tx.borrow().connected {
+                        backoff.reset();
+                    }
+                    let delay = backoff.next_delay();
+                    let reason = match result {
+                        Ok(()) => {
+                            warn!("Niri event stream ended — reconnecting in {delay:.1?}");
+                            "niri closed the event stream".to_string()
+                        }
+                        Err(e) => {
+                            error!("Event stream error: {e} — reconnecting in {delay:.1?}");
+                            e.to_string()
+                        }
+                    };
+                    self.mark_disconnected(reason).await;
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = socket.appeared() => info!("Niri socket appeared — reconnecting"),
+                        _ = self.resync.notified() => {}
+                    }
+                }
+                _ = self.resync.notified() => {
+                    info!("Resyncing Niri event stream");
+                }
             }
-            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
         }
     }
 
-    async fn connect_event_stream(&self) -> anyhow::Result<()> {
+    async fn connect_event_stream(self: &Arc<Self>) -> anyhow::Result<()> {
         let path = socket_path()?;
         let mut stream = UnixStream::connect(&path).await?;
         let req = serde_json::to_vec(&NiriRequest::EventStream)?;
@@ -63,6 +217,8 @@ impl NiriClient {
         reader.read_line(&mut line).await?;
         let reply: niri_ipc::Reply = serde_json::from_str(line.trim())?;
         reply.map_err(|e| anyhow::anyhow!("EventStream rejected: {e}"))?;
+        self.mark_connected().await;
+        self.check_version().await;
 
         // Niri sends full initial state as the first events automatically —
         // no need for separate Workspaces/Windows requests.
@@ -70,68 +226,613 @@ impl NiriClient {
             line.clear();
             let n = reader.read_line(&mut line).await?;
             if n == 0 { break; }
-            match serde_json::from_str::<NiriEvent>(line.trim()) {
-                Ok(event) => {
+            self.record(&line);
+            match parse_event(&line) {
+                Ok(Ok(event)) => {
                     self.apply_event(&event).await;
+                    // niri has no output events: workspaces showing up on a
+                    // monitor we haven't been told about (first connect,
+                    // hotplug) are the cue to ask. The stream doesn't wait
+                    // for the answer; `state_tx` says when it is in.
+                    if matches!(event, NiriEvent::WorkspacesChanged { .. })
+                        && self.state.read().await.has_undescribed_outputs()
+                    {
+                        self.spawn_refresh_outputs();
+                    }
                     let _ = self.event_tx.send(event);
                 }
+                // A newer niri than our niri-ipc: carry on without it.
+                Ok(Err(name)) => self.skip_unknown_event(name),
                 Err(e) => warn!("Failed to parse Niri event: {e}\nRaw: {line}"),
             }
         }
         Ok(())
     }
 
-    async fn apply_event(&self, event: &NiriEvent) {
-        let mut state = self.state.write().await;
-        match event {
-            NiriEvent::WorkspacesChanged { workspaces } => {
-                // workspaces is a Vec<Workspace> directly
-                state.focused_workspace_id = workspaces.iter().find(|w| w.is_focused).map(|w| w.id);
-                state.workspaces = workspaces.clone();
+    // ── Connection status ─────────────────────────────────────────────────────
+    // Kept in the state itself so `get_state`, `status` and snapshot
+    // subscribers all see it, and mirrored on `connection_tx` for watchers.
+
+    async fn mark_connected(&self) {
+        let reconnect = self.has_connected.swap(true, Ordering::Relaxed);
+        self.update_connection(|c| {
+            c.connected = true;
+            c.disconnected_since_ms = None;
+            c.reconnects += u32::from(reconnect);
+        }).await;
+        if reconnect {
+            // Focus from the old stream may name a window closed since; the
+            // new stream's window list says what is focused now.
+            self.state.write().await.focused_window_id = None;
+        }
+    }
+
+    async fn mark_disconnected(&self, reason: String) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        self.update_connection(|c| {
+            c.connected = false;
+            c.disconnected_since_ms.get_or_insert(now);
+            c.last_error = Some(reason);
+        }).await;
+    }
+
+    async fn update_connection(&self, update: impl FnOnce(&mut ConnectionStatus)) {
+        let status = {
+            let mut state = self.state.write().await;
+            update(&mut state.connection);
+            state.connection.clone()
+        };
+        self.connection_tx.send_replace(status);
+    }
+
+    /// Asks niri for its version and notes how it compares to the niri-ipc
+    /// this build speaks; a mismatch is logged once per connect.
+    async fn check_version(&self) {
+        let version = match self.request(NiriRequest::Version).await {
+            Ok(NiriResponse::Version(version)) => Some(version),
+            Ok(other) => {
+                warn!("Unexpected reply to Version: {other:?}");
+                None
             }
-            NiriEvent::WorkspaceActivated { id, focused } => {
-                let activated_id = *id;
-                let is_focused = *focused;
-                for ws in &mut state.workspaces {
-                    ws.is_active = ws.id == activated_id;
-                    if is_focused {
-                        ws.is_focused = ws.id == activated_id;
-                    }
-                }
-                if is_focused {
-                    state.focused_workspace_id = Some(activated_id);
-                }
+            Err(e) => {
+                warn!("Failed to ask niri for its version: {e}");
+                None
             }
-            NiriEvent::WindowsChanged { windows } => {
-                state.windows_by_workspace.clear();
-                // windows is a Vec<Window> directly
-                state.focused_window_id = windows.iter().find(|w| w.is_focused).map(|w| w.id);
-                for win in windows.clone() {
-                    let ws_id = win.workspace_id.unwrap_or(0);
-                    state.windows_by_workspace.entry(ws_id).or_default().push(win);
-                }
+        };
+        let compatibility = version.as_deref().map_or(Compatibility::Unknown, Compatibility::of);
+        match (compatibility, &version) {
+            (Compatibility::Matched, Some(v)) => info!("niri {v}, built against niri-ipc {NIRI_IPC_VERSION}"),
+            (Compatibility::Mismatched, Some(v)) => warn!(
+                "niri {v} differs from niri-ipc {NIRI_IPC_VERSION} this was built against: \
+                 events it doesn't know will be ignored"
+            ),
+            _ => {}
+        }
+        self.update_connection(|c| {
+            c.niri_version = version;
+            c.compatibility = compatibility;
+        }).await;
+    }
+
+    /// Warns about each kind of event niri-ipc doesn't know the first time
+    /// it shows up; after that it is dropped quietly.
+    fn skip_unknown_event(&self, name: String) {
+        if self.unknown_events.lock().unwrap().insert(name.clone()) {
+            warn!("Ignoring `{name}` events from niri: niri-ipc {NIRI_IPC_VERSION} doesn't know them");
+        }
+    }
+
+    /// Runs [`refresh_outputs`](Self::refresh_outputs) in the background,
+    /// unless one is already waiting on niri.
+    fn spawn_refresh_outputs(self: &Arc<Self>) {
+        if self.refreshing_outputs.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let client = Arc::clone(self);
+        tokio::spawn(async move {
+            client.refresh_outputs().await;
+            client.refreshing_outputs.store(false, Ordering::Relaxed);
+        });
+    }
+
+    /// Asks niri to describe its outputs and folds the answer into the state.
+    async fn refresh_outputs(&self) {
+        match self.request(NiriRequest::Outputs).await {
+            Ok(NiriResponse::Outputs(outputs)) => {
+                // Recorded as niri's raw reply so a replay ends up with the same outputs.
+                self.record(&serde_json::json!({ "Ok": { "Outputs": &outputs } }).to_string());
+                self.apply_outputs(&outputs).await;
             }
-            NiriEvent::WindowOpenedOrChanged { window } => {
-                let ws_id = window.workspace_id.unwrap_or(0);
-                for wins in state.windows_by_workspace.values_mut() {
-                    wins.retain(|w| w.id != window.id);
-                }
-                state.windows_by_workspace.entry(ws_id).or_default().push(window.clone());
+            Ok(other) => warn!("Unexpected reply to Outputs: {other:?}"),
+            Err(e) => warn!("Failed to fetch outputs: {e}"),
+        }
+    }
+
+    fn record(&self, raw: &str) {
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.lock().unwrap().record(raw) {
+                warn!("Failed to record Niri event: {e}");
             }
-            NiriEvent::WindowClosed { id } => {
-                for wins in state.windows_by_workspace.values_mut() {
-                    wins.retain(|w| w.id != *id);
-                }
+        }
+    }
+
+    /// Feeds a recording into the state and the broadcast channel instead of
+    /// a live stream, keeping the recorded gaps between events.
+    pub async fn run_replay(self: Arc<Self>, path: PathBuf) {
+        let events = match recording::read(&path) {
+            Ok(events) => events,
+            Err(e) => {
+                error!("Failed to read recording {}: {e}", path.display());
+                return;
             }
-            NiriEvent::WindowFocusChanged { id } => {
-                state.focused_window_id = *id;
-                for wins in state.windows_by_workspace.values_mut() {
-                    for w in wins.iter_mut() {
-                        w.is_focused = Some(w.id) == *id;
-                    }
+        };
+        info!("Replaying {} Niri events from {}", events.len(), path.display());
+        self.replay(events, true).await;
+        info!("Replay of {} finished", path.display());
+    }
+
+    /// Applies recorded events in order, optionally waiting out their timing.
+    pub async fn replay(&self, events: Vec<recording::RecordedEvent>, realtime: bool) {
+        // The recording stands in for a live stream.
+        self.mark_connected().await;
+        let started = tokio::time::Instant::now();
+        for entry in events {
+            if realtime {
+                tokio::time::sleep_until(started + entry.offset()).await;
+            }
+            match serde_json::from_value::<NiriEvent>(entry.event.clone()) {
+                Ok(event) => self.ingest(event).await,
+                Err(e) => match serde_json::from_value::<niri_ipc::Reply>(entry.event) {
+                    Ok(Ok(NiriResponse::Outputs(outputs))) => self.apply_outputs(&outputs).await,
+                    _ => warn!("Skipping unparseable recorded event at {}ms: {e}", entry.at_ms),
+                },
+            }
+        }
+    }
+
+    async fn ingest(&self, event: NiriEvent) {
+        self.apply_event(&event).await;
+        let _ = self.event_tx.send(event);
+    }
+
+    async fn apply_event(&self, event: &NiriEvent) {
+        reduce(&mut *self.state.write().await, event);
+    }
+
+    async fn apply_outputs(&self, outputs: &HashMap<String, Output>) {
+        set_outputs(&mut *self.state.write().await, outputs);
+        self.state_tx.send_replace(());
+    }
+}
+
+/// Parses one event line, or names the event if niri-ipc doesn't know it.
+/// Events are externally tagged: `{"WindowClosed":{"id":1}}`.
+fn parse_event(line: &str) -> serde_json::Result<Result<NiriEvent, String>> {
+    let event: serde_json::Value = serde_json::from_str(line.trim())?;
+    let name = match &event {
+        serde_json::Value::Object(map) if map.len() == 1 => map.keys().next(),
+        serde_json::Value::String(name) => Some(name),
+        _ => None,
+    };
+    match name {
+        Some(name) if !is_known_event(name) => Ok(Err(name.clone())),
+        _ => serde_json::from_value(event).map(Ok),
+    }
+}
+
+/// Watches the directory niri's socket lives in for it, or any new niri
+/// socket, to be created.
+struct SocketWatch {
+    watch: Option<DirWatch>,
+    expected: Option<OsString>,
+}
+
+impl SocketWatch {
+    fn new() -> Self {
+        let path = match socket_path() {
+            Ok(path) => path,
+            Err(_) => return Self { watch: None, expected: None },
+        };
+        let watch = path.parent().and_then(|dir| {
+            DirWatch::new([dir], WatchMask::CREATE | WatchMask::MOVED_TO)
+            .inspect_err(|e| debug!("Can't watch {} for niri's socket: {e}", dir.display()))
+            .ok()
+        });
+        Self { watch, expected: path.file_name().map(OsStr::to_owned) }
+    }
+
+    /// Resolves once a socket shows up. Never resolves if the directory
+    /// couldn't be watched, leaving the caller's timer to retry.
+    async fn appeared(&mut self) {
+        let Some(watch) = &mut self.watch else { return std::future::pending().await };
+        loop {
+            match watch.changed().await {
+                Ok(names) if names.iter().any(|n| Some(n) == self.expected.as_ref() || is_niri_socket_name(n)) => return,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Watching for niri's socket failed: {e}");
+                    return std::future::pending().await;
                 }
             }
-            _ => {}
         }
     }
 }
+
+// ── Request connection ────────────────────────────────────────────────────────
+// niri answers the requests on a connection one at a time, in order, so
+// replies are matched to callers first-in, first-out.
+
+type PendingReply = oneshot::Sender<serde_json::Result<niri_ipc::Reply>>;
+type ReplyRx = oneshot::Receiver<serde_json::Result<niri_ipc::Reply>>;
+
+struct RequestConn {
+    write: OwnedWriteHalf,
+    /// Callers waiting for a reply, oldest first; `None` once niri has
+    /// closed the connection.
+    pending: Arc<Mutex<Option<VecDeque<PendingReply>>>>,
+    reader: JoinHandle<()>,
+}
+
+impl RequestConn {
+    async fn connect(path: &Path) -> std::io::Result<Self> {
+        let (read, write) = UnixStream::connect(path).await?.into_split();
+        debug!("Opened Niri request connection to {}", path.display());
+
+        let pending = Arc::new(Mutex::new(Some(VecDeque::<PendingReply>::new())));
+        let queue = Arc::clone(&pending);
+        let reader = tokio::spawn(async move {
+            let mut lines = BufReader::new(read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let waiter = queue.lock().unwrap().as_mut().and_then(VecDeque::pop_front);
+                let Some(waiter) = waiter else {
+                    warn!("Unsolicited reply from Niri: {line}");
+                    continue;
+                };
+                // A caller that timed out has dropped its receiver; its reply
+                // is simply discarded.
+                let _ = waiter.send(serde_json::from_str(&line));
+            }
+            // Dropping the senders fails everyone still waiting.
+            queue.lock().unwrap().take();
+        });
+        Ok(Self { write, pending, reader })
+    }
+
+    /// Queues a reply slot and writes the request. `None` means the
+    /// connection is gone and the request wasn't sent.
+    async fn send(&mut self, payload: &[u8]) -> Option<ReplyRx> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().as_mut()?.push_back(tx);
+        self.write.write_all(payload).await.ok()?;
+        Some(rx)
+    }
+}
+
+impl Drop for RequestConn {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::mock::{eventually, window, workspace, MockNiri};
+
+    fn spawn_stream() -> (Arc<NiriClient>, broadcast::Receiver<NiriEvent>) {
+        let (client, rx) = NiriClient::new();
+        let client = Arc::new(client);
+        tokio::spawn(Arc::clone(&client).run_event_stream());
+        (client, rx)
+    }
+
+    #[tokio::test]
+    async fn request_round_trips_through_the_socket() {
+        let niri = MockNiri::start().await;
+        let (client, _rx) = NiriClient::new();
+
+        let reply = client.request(NiriRequest::Action(Action::FocusWindow { id: 7 })).await.unwrap();
+
+        assert!(matches!(reply, NiriResponse::Handled));
+        assert!(matches!(
+            niri.requests()[..],
+            [NiriRequest::Action(Action::FocusWindow { id: 7 })]
+        ));
+    }
+
+    #[tokio::test]
+    async fn request_surfaces_niri_errors() {
+        let _niri = MockNiri::with_responder(|_| Err("no such window".into())).await;
+        let (client, _rx) = NiriClient::new();
+
+        let err = client
+        .request(NiriRequest::Action(Action::CloseWindow { id: Some(1) }))
+        .await.unwrap_err();
+
+        assert_eq!(err, CommandError::NiriRejected { reason: "no such window".into() });
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_share_one_pipelined_connection() {
+        let niri = MockNiri::with_responder(|req| match req {
+            NiriRequest::Action(Action::FocusWindow { id }) => Err(format!("window {id}")),
+            _ => Ok(NiriResponse::Handled),
+        }).await;
+        let (client, _rx) = NiriClient::new();
+        let focus = |id| client.request(NiriRequest::Action(Action::FocusWindow { id }));
+
+        let (a, b, c) = tokio::join!(focus(1), focus(2), focus(3));
+        let d = focus(4).await;
+
+        for (id, reply) in [(1, a), (2, b), (3, c), (4, d)] {
+            assert_eq!(reply.unwrap_err(), CommandError::NiriRejected { reason: format!("window {id}") });
+        }
+        assert_eq!(niri.connection_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn request_reconnects_when_niri_drops_the_connection() {
+        let niri = MockNiri::start().await;
+        let (client, _rx) = NiriClient::new();
+        client.request(NiriRequest::Action(Action::FocusWindow { id: 1 })).await.unwrap();
+
+        niri.drop_connections();
+        let client_ref = &client;
+        eventually("the client to see the close", || async move {
+            let conn = client_ref.requests.lock().await;
+            conn.as_ref().is_some_and(|conn| conn.pending.lock().unwrap().is_none())
+        }).await;
+        client.request(NiriRequest::Action(Action::FocusWindow { id: 2 })).await.unwrap();
+
+        assert_eq!(niri.connection_count(), 2);
+        assert_eq!(niri.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn request_times_out_when_niri_hangs() {
+        let niri = MockNiri::start().await;
+        let (mut client, _rx) = NiriClient::new();
+        client.request_timeout = Duration::from_millis(100);
+        niri.hang_requests();
+
+        let err = client.request(NiriRequest::Action(Action::FocusWindow { id: 1 })).await.unwrap_err();
+
+        assert_eq!(err, CommandError::Timeout { ms: 100 });
+    }
+
+    #[tokio::test]
+    async fn a_timed_out_request_leaves_the_others_alone() {
+        let niri = MockNiri::with_responder(|req| match req {
+            NiriRequest::Action(Action::FocusWindow { id }) => Err(format!("window {id}")),
+            _ => Ok(NiriResponse::Handled),
+        }).await;
+        let (mut client, _rx) = NiriClient::new();
+        client.request_timeout = Duration::from_millis(400);
+        niri.delay_next_reply(Duration::from_millis(600));
+        let focus = |id| client.request(NiriRequest::Action(Action::FocusWindow { id }));
+
+        let (stalled, queued) = tokio::join!(focus(1), async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            focus(2).await
+        });
+        let after = focus(3).await;
+
+        assert_eq!(stalled.unwrap_err(), CommandError::Timeout { ms: 400 });
+        // Each caller still gets its own reply, the late one went nowhere.
+        for (id, reply) in [(2, queued), (3, after)] {
+            assert_eq!(reply.unwrap_err(), CommandError::NiriRejected { reason: format!("window {id}") });
+        }
+        assert_eq!(niri.connection_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn lagging_subscribers_get_a_snapshot_and_carry_on() {
+        let (client, mut rx) = NiriClient::new();
+        client.state.write().await.focused_window_id = Some(7);
+        for id in 0..300 {
+            let _ = client.event_tx.send(NiriEvent::WindowClosed { id });
+        }
+
+        let Some(Received::Snapshot(state)) = client.next_event(&mut rx).await else {
+            panic!("expected a snapshot after lagging");
+        };
+        assert_eq!(state.focused_window_id, Some(7));
+        assert_eq!(client.lagged_events(), 44);
+        let next = client.next_event(&mut rx).await;
+        assert!(matches!(next, Some(Received::Event(NiriEvent::WindowClosed { id: 44 }))));
+    }
+
+    #[tokio::test]
+    async fn event_stream_seeds_state_and_forwards_events() {
+        let niri = MockNiri::start().await;
+        niri.set_initial_events(vec![
+            NiriEvent::WorkspacesChanged { workspaces: vec![workspace(1, 1), workspace(2, 2)] },
+            NiriEvent::WindowsChanged { windows: vec![window(10, 1), window(11, 2)] },
+        ]);
+        let (client, mut rx) = spawn_stream();
+
+        assert!(matches!(rx.recv().await.unwrap(), NiriEvent::WorkspacesChanged { .. }));
+        let c = &client;
+        eventually("initial windows", || async move { c.state.read().await.total_windows() == 2 }).await;
+        assert_eq!(client.state.read().await.focused_workspace_id, Some(1));
+
+        niri.emit(NiriEvent::WindowClosed { id: 10 });
+        eventually("window 10 to close", || async move { c.state.read().await.total_windows() == 1 }).await;
+    }
+
+    #[tokio::test]
+    async fn event_stream_describes_new_outputs() {
+        let niri = MockNiri::start().await;
+        niri.set_initial_events(vec![
+            NiriEvent::WorkspacesChanged { workspaces: vec![workspace(1, 1), workspace(2, 2)] },
+        ]);
+        let (client, _rx) = NiriClient::new();
+        let client = Arc::new(client);
+        let mut refreshed = client.state_tx.subscribe();
+        tokio::spawn(Arc::clone(&client).run_event_stream());
+
+        refreshed.changed().await.unwrap();
+        let state = client.state.read().await;
+        assert_eq!(state.outputs.len(), 1);
+        assert_eq!(state.outputs[0].make.as_deref(), Some("Mock"));
+        assert_eq!(state.outputs[0].workspace_ids, [1, 2]);
+        assert_eq!(state.outputs[0].active_workspace_id, Some(1));
+        assert!(matches!(niri.requests()[..], [NiriRequest::Outputs]));
+    }
+
+    #[tokio::test]
+    async fn event_stream_does_not_wait_for_outputs() {
+        let niri = MockNiri::start().await;
+        niri.set_initial_events(vec![
+            NiriEvent::WorkspacesChanged { workspaces: vec![workspace(1, 1)] },
+        ]);
+        niri.hang_requests();
+        let (client, mut rx) = spawn_stream();
+        let limit = Duration::from_secs(1);
+
+        let first = tokio::time::timeout(limit, rx.recv()).await.expect("the event was held back");
+        assert!(matches!(first.unwrap(), NiriEvent::WorkspacesChanged { .. }));
+        niri.emit(NiriEvent::WindowClosed { id: 10 });
+        let next = tokio::time::timeout(limit, rx.recv()).await.expect("the stream stalled");
+        assert!(matches!(next.unwrap(), NiriEvent::WindowClosed { id: 10 }));
+        assert!(client.state.read().await.has_undescribed_outputs());
+    }
+
+    #[tokio::test]
+    async fn event_stream_checks_niri_version_and_skips_unknown_events() {
+        let niri = MockNiri::start().await;
+        niri.set_version("26.04 (abcdef0)");
+        let (client, mut rx) = spawn_stream();
+
+        let mut connection = client.connection_tx.subscribe();
+        let status = connection.wait_for(|c| c.niri_version.is_some()).await.unwrap().clone();
+        assert_eq!(status.niri_version.as_deref(), Some("26.04 (abcdef0)"));
+        assert_eq!(status.compatibility, Compatibility::Mismatched);
+
+        niri.emit_raw(r#"{"SomethingNew":{"id":1}}"#);
+        niri.emit(NiriEvent::WindowClosed { id: 10 });
+        assert!(matches!(rx.recv().await.unwrap(), NiriEvent::WindowClosed { id: 10 }));
+    }
+
+    #[test]
+    fn unknown_events_are_told_apart_from_malformed_ones() {
+        assert!(matches!(parse_event(r#"{"WindowClosed":{"id":1}}"#), Ok(Ok(NiriEvent::WindowClosed { id: 1 }))));
+        assert_eq!(parse_event(r#"{"SomethingNew":{"id":1}}"#).unwrap().unwrap_err(), "SomethingNew");
+        assert_eq!(parse_event(r#""AlsoNew""#).unwrap().unwrap_err(), "AlsoNew");
+        assert!(parse_event(r#"{"WindowClosed":{}}"#).is_err());
+        assert!(parse_event("not json").is_err());
+    }
+
+    #[test]
+    fn compatibility_compares_niri_releases() {
+        assert_eq!(Compatibility::of("25.11 (b35bcae)"), Compatibility::Matched);
+        assert_eq!(Compatibility::of("niri 25.11.1"), Compatibility::Matched);
+        assert_eq!(Compatibility::of("25.08"), Compatibility::Mismatched);
+        assert_eq!(Compatibility::of("unstable"), Compatibility::Unknown);
+    }
+
+    #[test]
+    fn niri_ipc_version_matches_the_cargo_pin() {
+        let manifest = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap();
+        let pin = manifest.lines().find_map(|line| line.trim().strip_prefix("niri-ipc = "));
+        assert_eq!(pin, Some(format!(r#""={NIRI_IPC_VERSION}""#).as_str()));
+    }
+
+    #[tokio::test]
+    async fn event_stream_reconnects_when_niri_drops_it() {
+        let niri = MockNiri::start().await;
+        niri.set_initial_events(vec![NiriEvent::WindowsChanged { windows: vec![window(10, 1)] }]);
+        let (client, _rx) = spawn_stream();
+        let c = &client;
+        eventually("first stream", || async move { c.state.read().await.total_windows() == 1 }).await;
+
+        niri.set_initial_events(vec![NiriEvent::WindowsChanged {
+            windows: vec![window(10, 1), window(12, 1)],
+        }]);
+        let mut connection = client.connection_tx.subscribe();
+        niri.drop_connections();
+
+        connection.wait_for(|c| !c.connected).await.unwrap();
+        let stale = c.state.read().await.connection.clone();
+        assert!(stale.disconnected_since_ms.is_some());
+        assert_eq!(stale.last_error.as_deref(), Some("niri closed the event stream"));
+
+        eventually("state from the new stream", || async move {
+            c.state.read().await.total_windows() == 2
+        }).await;
+        assert_eq!(niri.event_stream_count(), 2);
+        let live = c.state.read().await.connection.clone();
+        assert!(live.connected && live.disconnected_since_ms.is_none());
+        assert_eq!(live.reconnects, 1);
+    }
+
+    #[tokio::test]
+    async fn event_stream_reconnects_as_soon_as_the_socket_returns() {
+        let mut niri = MockNiri::start().await;
+        let (client, _rx) = spawn_stream();
+        let mut connection = client.connection_tx.subscribe();
+        connection.wait_for(|c| c.connected).await.unwrap();
+
+        niri.stop();
+        // The stream ending, then two failed attempts: the next retry is
+        // seconds away.
+        for _ in 0..3 {
+            connection.changed().await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        niri.restart();
+
+        tokio::time::timeout(Duration::from_secs(1), connection.wait_for(|c| c.connected))
+        .await
+        .expect("reconnected before the backoff ran out")
+        .unwrap();
+        assert_eq!(niri.event_stream_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn recording_replays_to_the_same_state() {
+        let niri = MockNiri::start().await;
+        niri.set_initial_events(vec![
+            NiriEvent::WorkspacesChanged { workspaces: vec![workspace(1, 1), workspace(2, 2)] },
+            NiriEvent::WindowsChanged { windows: vec![window(10, 1), window(11, 2)] },
+        ]);
+        let path = std::env::temp_dir().join(format!("niri-switch-recording-{}.jsonl", std::process::id()));
+        let (mut live, _rx) = NiriClient::new();
+        live.record_to(&path).unwrap();
+        let live = Arc::new(live);
+        tokio::spawn(Arc::clone(&live).run_event_stream());
+
+        let c = &live;
+        eventually("initial windows", || async move { c.state.read().await.total_windows() == 2 }).await;
+        niri.emit(NiriEvent::WindowClosed { id: 10 });
+        eventually("window 10 to close", || async move { c.state.read().await.total_windows() == 1 }).await;
+
+        let recorded = recording::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        // Three events plus niri's reply describing the outputs.
+        assert_eq!(recorded.len(), 4);
+        let (replayed, _rx) = NiriClient::new();
+        replayed.replay(recorded, false).await;
+
+        // A replay never asks niri for its version.
+        let mut expected = live.state.read().await.clone();
+        expected.connection.niri_version = None;
+        expected.connection.compatibility = Compatibility::Unknown;
+        assert_eq!(
+            serde_json::to_value(&*replayed.state.read().await).unwrap(),
+            serde_json::to_value(&expected).unwrap(),
+        );
+    }
+
+    #[tokio::test]
+    async fn resync_reopens_the_event_stream_immediately() {
+        let niri = MockNiri::start().await;
+        let (client, _rx) = spawn_stream();
+        let niri_ref = &niri;
+        eventually("first stream", || async move { niri_ref.event_stream_count() == 1 }).await;
+
+        client.resync();
+
+        eventually("second stream", || async move { niri_ref.event_stream_count() == 2 }).await;
+    }
+}
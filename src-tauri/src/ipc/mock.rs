@@ -0,0 +1,312 @@
+//! Scripted stand-in for niri's IPC socket, for tests.
+//!
+//! `MockNiri` binds a Unix socket in the temp dir and points `NIRI_SOCKET` at
+//! it, so `NiriClient` talks to it exactly as it would to the compositor:
+//! plain requests get a scripted [`Reply`] (one per line, pipelined like
+//! niri), and `EventStream` requests get the configured initial events
+//! followed by whatever the test [`emit`](MockNiri::emit)s. `Version` is
+//! answered with [`set_version`](MockNiri::set_version)'s string.
+//!
+//! `NIRI_SOCKET` is process-global, so a `MockNiri` holds a lock for its
+//! whole lifetime and tests using it run one at a time.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use niri_ipc::{
+    Event, LogicalOutput, Output, Reply, Request, Response, Transform, Window, WindowLayout, Workspace,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::broadcast,
+    task::JoinHandle,
+};
+
+/// Held by tests that point niri's or our own socket somewhere else.
+pub(crate) static ENV_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+type Responder = dyn Fn(&Request) -> Reply + Send + Sync;
+
+pub struct MockNiri {
+    path: PathBuf,
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+    _env: tokio::sync::MutexGuard<'static, ()>,
+}
+
+struct Shared {
+    responder: Box<Responder>,
+    initial_events: Mutex<Vec<Event>>,
+    requests: Mutex<Vec<Request>>,
+    /// Event lines, already serialized, so tests can send ones niri-ipc
+    /// doesn't know.
+    events: broadcast::Sender<String>,
+    version: Mutex<String>,
+    kick: broadcast::Sender<()>,
+    connections: AtomicUsize,
+    event_streams: AtomicUsize,
+    hang: AtomicBool,
+    delay_next: Mutex<Option<Duration>>,
+}
+
+impl MockNiri {
+    /// A mock that describes a single `MOCK-1` output when asked and answers
+    /// every other request with `Ok(Response::Handled)`.
+    pub async fn start() -> Self {
+        Self::with_responder(|req| match req {
+            Request::Outputs => Ok(Response::Outputs([("MOCK-1".into(), output("MOCK-1", 0))].into())),
+            _ => Ok(Response::Handled),
+        }).await
+    }
+
+    /// A mock that answers requests with `responder`.
+    pub async fn with_responder(responder: impl Fn(&Request) -> Reply + Send + Sync + 'static) -> Self {
+        let env = ENV_LOCK.lock().await;
+
+        let path = std::env::temp_dir().join(format!(
+            "niri-switch-mock-{}-{}.sock",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        ));
+        let _ = std::fs::remove_file(&path);
+        std::env::set_var("NIRI_SOCKET", &path);
+
+        let shared = Arc::new(Shared {
+            responder: Box::new(responder),
+            initial_events: Mutex::new(Vec::new()),
+            requests: Mutex::new(Vec::new()),
+            events: broadcast::channel(64).0,
+            version: Mutex::new("25.11 (mock)".into()),
+            kick: broadcast::channel(4).0,
+            connections: AtomicUsize::new(0),
+            event_streams: AtomicUsize::new(0),
+            hang: AtomicBool::new(false),
+            delay_next: Mutex::new(None),
+        });
+
+        let task = listen(&path, &shared);
+        Self { path, shared, task, _env: env }
+    }
+
+    /// Closes every connection and removes the socket, as if niri exited.
+    pub fn stop(&mut self) {
+        self.task.abort();
+        self.drop_connections();
+        let _ = std::fs::remove_file(&self.path);
+    }
+
+    /// Brings the socket back at the same path after [`stop`](Self::stop).
+    pub fn restart(&mut self) {
+        self.task = listen(&self.path, &self.shared);
+    }
+
+    /// Events every new event stream receives first, like niri's initial
+    /// `WorkspacesChanged`/`WindowsChanged` burst.
+    pub fn set_initial_events(&self, events: Vec<Event>) {
+        *self.shared.initial_events.lock().unwrap() = events;
+    }
+
+    /// Sends an event to every connected event stream.
+    pub fn emit(&self, event: Event) {
+        self.emit_raw(&serde_json::to_string(&event).unwrap());
+    }
+
+    /// Sends a raw event line, e.g. one from a newer niri.
+    pub fn emit_raw(&self, line: &str) {
+        let _ = self.shared.events.send(line.to_string());
+    }
+
+    /// What the mock answers `Version` with.
+    pub fn set_version(&self, version: &str) {
+        *self.shared.version.lock().unwrap() = version.to_string();
+    }
+
+    /// Closes every open connection, event streams included, as if niri
+    /// restarted.
+    pub fn drop_connections(&self) {
+        let _ = self.shared.kick.send(());
+    }
+
+    /// Stops answering plain requests (they are still recorded), like a
+    /// compositor that has hung.
+    pub fn hang_requests(&self) {
+        self.shared.hang.store(true, Ordering::SeqCst);
+    }
+
+    /// Holds back the reply to the next plain request by `delay`. niri
+    /// answers in order, so the replies queued behind it wait too.
+    pub fn delay_next_reply(&self, delay: Duration) {
+        *self.shared.delay_next.lock().unwrap() = Some(delay);
+    }
+
+    /// Every request received so far, in order, except `EventStream` and the
+    /// client's `Version` handshake.
+    pub fn requests(&self) -> Vec<Request> {
+        self.shared.requests.lock().unwrap().clone()
+    }
+
+    /// How many connections have been opened so far, of any kind.
+    pub fn connection_count(&self) -> usize {
+        self.shared.connections.load(Ordering::SeqCst)
+    }
+
+    /// How many event streams have been opened so far.
+    pub fn event_stream_count(&self) -> usize {
+        self.shared.event_streams.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockNiri {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn listen(path: &std::path::Path, shared: &Arc<Shared>) -> JoinHandle<()> {
+    let listener = UnixListener::bind(path).expect("failed to bind mock niri socket");
+    let shared = Arc::clone(shared);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve(stream, Arc::clone(&shared)));
+        }
+    })
+}
+
+async fn serve(stream: UnixStream, shared: Arc<Shared>) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let mut kick = shared.kick.subscribe();
+    shared.connections.fetch_add(1, Ordering::SeqCst);
+
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => line,
+                _ => return,
+            },
+            _ = kick.recv() => return,
+        };
+        let reply = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::EventStream) => {
+                // Subscribe before writing anything so no emitted event is missed.
+                let mut events = shared.events.subscribe();
+                shared.event_streams.fetch_add(1, Ordering::SeqCst);
+
+                let initial = shared.initial_events.lock().unwrap().clone();
+                if write_json(&mut write, &Reply::Ok(Response::Handled)).await.is_err() { return; }
+                for event in &initial {
+                    if write_json(&mut write, event).await.is_err() { return; }
+                }
+                loop {
+                    tokio::select! {
+                        Ok(line) = events.recv() => {
+                            if write_line(&mut write, &line).await.is_err() { return; }
+                        }
+                        _ = kick.recv() => return,
+                    }
+                }
+            }
+            Ok(Request::Version) => Ok(Response::Version(shared.version.lock().unwrap().clone())),
+            Ok(req) => {
+                let reply = (shared.responder)(&req);
+                shared.requests.lock().unwrap().push(req);
+                if shared.hang.load(Ordering::SeqCst) { continue; }
+                let delay = shared.delay_next.lock().unwrap().take();
+                if let Some(delay) = delay {
+                    tokio::time::sleep(delay).await;
+                }
+                reply
+            }
+            Err(e) => Err(format!("error parsing request: {e}")),
+        };
+        if write_json(&mut write, &reply).await.is_err() { return; }
+    }
+}
+
+async fn write_json<T: serde::Serialize>(
+    write: &mut tokio::net::unix::OwnedWriteHalf,
+    value: &T,
+) -> std::io::Result<()> {
+    write_line(write, &serde_json::to_string(value)?).await
+}
+
+async fn write_line(write: &mut tokio::net::unix::OwnedWriteHalf, line: &str) -> std::io::Result<()> {
+    write.write_all(format!("{line}\n").as_bytes()).await
+}
+
+// ── Fixtures ──────────────────────────────────────────────────────────────────
+
+pub fn window(id: u64, workspace_id: u64) -> Window {
+    Window {
+        id,
+        title: Some(format!("window {id}")),
+        app_id: Some("test.app".into()),
+        pid: None,
+        workspace_id: Some(workspace_id),
+        is_focused: false,
+        is_floating: false,
+        is_urgent: false,
+        layout: WindowLayout {
+            pos_in_scrolling_layout: None,
+            tile_size: (0.0, 0.0),
+            window_size: (0, 0),
+            tile_pos_in_workspace_view: None,
+            window_offset_in_tile: (0.0, 0.0),
+        },
+        focus_timestamp: None,
+    }
+}
+
+pub fn output(name: &str, x: i32) -> Output {
+    Output {
+        name: name.into(),
+        make: "Mock".into(),
+        model: "Display".into(),
+        serial: None,
+        physical_size: None,
+        modes: Vec::new(),
+        current_mode: None,
+        is_custom_mode: false,
+        vrr_supported: false,
+        vrr_enabled: false,
+        logical: Some(LogicalOutput {
+            x,
+            y: 0,
+            width: 1920,
+            height: 1080,
+            scale: 1.0,
+            transform: Transform::Normal,
+        }),
+    }
+}
+
+pub fn workspace(id: u64, idx: u8) -> Workspace {
+    Workspace {
+        id,
+        idx,
+        name: None,
+        output: Some("MOCK-1".into()),
+        is_urgent: false,
+        is_active: idx == 1,
+        is_focused: idx == 1,
+        active_window_id: None,
+    }
+}
+
+/// Polls `check` until it returns true, panicking after a few seconds.
+pub async fn eventually<F, Fut>(what: &str, mut check: F)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while !check().await {
+        assert!(tokio::time::Instant::now() < deadline, "timed out waiting for {what}");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
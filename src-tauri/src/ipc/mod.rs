@@ -1,5 +1,10 @@
+pub mod backoff;
 pub mod client;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod recording;
+pub mod reducer;
 pub mod types;
 
-pub use client::NiriClient;
-pub use types::{NiriEvent, NiriRequest, NiriState};
+pub use client::{NiriClient, Received};
+pub use types::{Compatibility, ConnectionStatus, NiriEvent, NiriRequest, NiriState};
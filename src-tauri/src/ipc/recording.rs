@@ -0,0 +1,151 @@
+//! Recording and replaying the raw niri event stream.
+//!
+//! A recording is JSON lines, one per event line niri sent:
+//! `{"unix_ms":1760000000000,"at_ms":1234,"event":{"WindowClosed":{"id":7}}}`.
+//! niri's replies to the daemon's `Outputs` requests are recorded the same
+//! way (`"event":{"Ok":{"Outputs":{...}}}`), since no event carries them.
+//! `at_ms` counts from when recording started and drives replay timing;
+//! `unix_ms` is there to line a recording up with a user's bug report.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub unix_ms: u64,
+    pub at_ms: u64,
+    /// The event exactly as niri sent it. Kept as raw JSON so events this
+    /// build can't parse are still captured.
+    pub event: Value,
+}
+
+pub struct Recorder {
+    file: std::fs::File,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Creates (or truncates) the recording at `path`.
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Self { file: std::fs::File::create(path)?, started: Instant::now() })
+    }
+
+    /// Appends one raw event line. Written straight through, so a recording
+    /// survives the daemon crashing on the very event that triggered the bug.
+    pub fn record(&mut self, raw: &str) -> std::io::Result<()> {
+        let raw = raw.trim();
+        let entry = RecordedEvent {
+            unix_ms: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64),
+            at_ms: self.started.elapsed().as_millis() as u64,
+            event: serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string())),
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)
+    }
+}
+
+/// Reads a whole recording. Malformed lines are an error: a replay that
+/// silently skips events would reproduce the wrong state. The exception is
+/// the last line, which a daemon killed mid-write leaves cut short.
+pub fn read(path: &Path) -> anyhow::Result<Vec<RecordedEvent>> {
+    let file = std::fs::File::open(path)?;
+    let mut lines = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            lines.push((n + 1, line));
+        }
+    }
+
+    let mut events = Vec::with_capacity(lines.len());
+    let last = lines.len();
+    for (i, (n, line)) in lines.into_iter().enumerate() {
+        match serde_json::from_str(&line) {
+            Ok(entry) => events.push(entry),
+            Err(e) if i + 1 == last => warn!("{}:{n}: skipping truncated last line: {e}", path.display()),
+            Err(e) => anyhow::bail!("{}:{n}: {e}", path.display()),
+        }
+    }
+    Ok(events)
+}
+
+impl RecordedEvent {
+    pub fn offset(&self) -> Duration {
+        Duration::from_millis(self.at_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::mock::{output, window, workspace};
+    use crate::ipc::{NiriClient, NiriEvent};
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("niri-switch-recording-{}-{name}.jsonl", std::process::id()))
+    }
+
+    fn event_line(event: &NiriEvent) -> String {
+        serde_json::to_string(event).unwrap()
+    }
+
+    #[tokio::test]
+    async fn events_and_outputs_replies_round_trip() {
+        let path = temp_file("round-trip");
+        let mut recorder = Recorder::create(&path).unwrap();
+        let outputs = serde_json::json!({ "Ok": { "Outputs": { "MOCK-1": output("MOCK-1", 0) } } });
+        for line in [
+            event_line(&NiriEvent::WorkspacesChanged { workspaces: vec![workspace(1, 1)] }),
+            outputs.to_string(),
+            event_line(&NiriEvent::WindowsChanged { windows: vec![window(10, 1), window(11, 1)] }),
+            // An event from a newer niri, kept as niri sent it.
+            r#"{"SomethingNew":{"id":1}}"#.to_string(),
+            event_line(&NiriEvent::WindowClosed { id: 10 }),
+        ] {
+            recorder.record(&format!("{line}\n")).unwrap();
+        }
+
+        let recorded = read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(recorded.len(), 5);
+        assert_eq!(recorded[1].event, outputs);
+        assert!(recorded.windows(2).all(|pair| pair[0].at_ms <= pair[1].at_ms));
+
+        let (client, _rx) = NiriClient::new();
+        client.replay(recorded, false).await;
+        let state = client.state.read().await;
+        assert_eq!(state.total_windows(), 1);
+        assert!(state.has_window(11));
+        assert_eq!(state.outputs.len(), 1);
+        assert_eq!(state.outputs[0].model.as_deref(), Some("Display"));
+    }
+
+    #[test]
+    fn a_truncated_last_line_is_skipped() {
+        let path = temp_file("truncated");
+        let entry = |at_ms, event: &NiriEvent| serde_json::to_string(&RecordedEvent {
+            unix_ms: 0,
+            at_ms,
+            event: serde_json::to_value(event).unwrap(),
+        }).unwrap();
+        let whole = [
+            entry(0, &NiriEvent::WindowClosed { id: 1 }),
+            entry(5, &NiriEvent::WindowClosed { id: 2 }),
+        ].join("\n");
+
+        std::fs::write(&path, format!("{whole}\n{{\"unix_ms\":0,\"at_ms\":9,\"event\":{{\"Wind\n\n")).unwrap();
+        let events = read(&path).unwrap();
+        assert_eq!(events.iter().map(|e| e.at_ms).collect::<Vec<_>>(), [0, 5]);
+
+        std::fs::write(&path, format!("not json\n{whole}\n")).unwrap();
+        let err = read(&path).unwrap_err().to_string();
+        let _ = std::fs::remove_file(&path);
+        assert!(err.contains(":1: "), "{err}");
+    }
+}
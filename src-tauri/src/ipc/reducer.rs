@@ -0,0 +1,439 @@
+//! Folding niri events into [`NiriState`].
+//!
+//! Kept free of I/O and locking so it can be tested against recorded event
+//! sequences. The overlay renders the state this produces, sent to it on
+//! `niri://state`.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use super::types::{is_own_window, NiriEvent, NiriState, Output, OutputState, Window, Workspace};
+
+/// Windows with no workspace (e.g. while niri is moving them between
+/// outputs) are kept under this key rather than dropped.
+pub const NO_WORKSPACE: u64 = 0;
+
+pub fn reduce(state: &mut NiriState, event: &NiriEvent) {
+    // No catch-all arm on purpose: an event added in a niri-ipc bump should
+    // fail to compile here rather than be silently dropped.
+    match event {
+        NiriEvent::WorkspacesChanged { workspaces } => {
+            state.focused_workspace_id = workspaces.iter().find(|w| w.is_focused).map(|w| w.id);
+            state.workspaces = workspaces.clone();
+            group_by_output(state);
+        }
+        NiriEvent::WorkspaceActivated { id, focused } => {
+            // Activation is per output; focus is global.
+            let output = state.workspaces.iter().find(|ws| ws.id == *id).map(|ws| ws.output.clone());
+            for ws in &mut state.workspaces {
+                if output.as_ref() == Some(&ws.output) {
+                    ws.is_active = ws.id == *id;
+                }
+                if *focused {
+                    ws.is_focused = ws.id == *id;
+                }
+            }
+            if *focused {
+                state.focused_workspace_id = Some(*id);
+            }
+            group_by_output(state);
+        }
+        NiriEvent::WindowsChanged { windows } => {
+            // A focus change can arrive before the full window list (e.g. right
+            // after connecting), for a window opened after the list was taken.
+            // Keep that focus until the window shows up; for any window the
+            // list does contain, its `is_focused` is the newer word.
+            let focused = state.focused_window_id.filter(|id| !windows.iter().any(|w| w.id == *id))
+            .or(windows.iter().find(|w| w.is_focused).map(|w| w.id));
+
+            state.windows_by_workspace.clear();
+            for win in windows {
+                let mut win = win.clone();
+                win.is_focused = Some(win.id) == focused;
+                state.windows_by_workspace.entry(bucket(&win)).or_default().push(win);
+            }
+            state.focused_window_id = focused;
+            if let Some(id) = focused {
+                touch_mru(state, id);
+            }
+            sync_mru(state);
+            sort_by_layout(state);
+        }
+        NiriEvent::WindowOpenedOrChanged { window } => {
+            remove_window(state, window.id);
+            if window.is_focused {
+                set_focused_window(state, Some(window.id));
+            }
+            let mut window = window.clone();
+            window.is_focused = state.focused_window_id == Some(window.id);
+            state.windows_by_workspace.entry(bucket(&window)).or_default().push(window);
+            sync_mru(state);
+            sort_by_layout(state);
+        }
+        NiriEvent::WindowClosed { id } => {
+            remove_window(state, *id);
+            if state.focused_window_id == Some(*id) {
+                state.focused_window_id = None;
+            }
+            sync_mru(state);
+            sort_by_layout(state);
+        }
+        NiriEvent::WindowFocusChanged { id } => {
+            set_focused_window(state, *id);
+            sync_mru(state);
+        }
+        NiriEvent::WorkspaceUrgencyChanged { id, urgent } => {
+            if let Some(ws) = state.workspace_mut(*id) {
+                ws.is_urgent = *urgent;
+            }
+        }
+        NiriEvent::WorkspaceActiveWindowChanged { workspace_id, active_window_id } => {
+            if let Some(ws) = state.workspace_mut(*workspace_id) {
+                ws.active_window_id = *active_window_id;
+            }
+        }
+        NiriEvent::WindowFocusTimestampChanged { id, focus_timestamp } => {
+            if let Some(win) = state.window_mut(*id) {
+                win.focus_timestamp = *focus_timestamp;
+            }
+        }
+        NiriEvent::WindowUrgencyChanged { id, urgent } => {
+            if let Some(win) = state.window_mut(*id) {
+                win.is_urgent = *urgent;
+            }
+        }
+        NiriEvent::WindowLayoutsChanged { changes } => {
+            for (id, layout) in changes {
+                if let Some(win) = state.window_mut(*id) {
+                    win.layout = layout.clone();
+                }
+            }
+            sort_by_layout(state);
+        }
+        NiriEvent::KeyboardLayoutsChanged { keyboard_layouts } => {
+            state.keyboard_layouts = Some(keyboard_layouts.clone());
+        }
+        NiriEvent::KeyboardLayoutSwitched { idx } => {
+            if let Some(layouts) = &mut state.keyboard_layouts {
+                layouts.current_idx = *idx;
+            }
+        }
+        NiriEvent::OverviewOpenedOrClosed { is_open } => state.overview_open = *is_open,
+        NiriEvent::ConfigLoaded { failed } => state.config_failed = *failed,
+        // Nothing to keep: subscribers see it on the raw event stream.
+        NiriEvent::ScreenshotCaptured { .. } => {}
+    }
+}
+
+/// Replaces the output details with niri's reply to `Request::Outputs`.
+pub fn set_outputs(state: &mut NiriState, outputs: &HashMap<String, Output>) {
+    state.outputs = outputs.values().map(|o| OutputState {
+        name: o.name.clone(),
+        make: Some(o.make.clone()),
+        model: Some(o.model.clone()),
+        logical: o.logical,
+        workspace_ids: Vec::new(),
+        active_workspace_id: None,
+    }).collect();
+    group_by_output(state);
+}
+
+// ── Outputs ───────────────────────────────────────────────────────────────────
+
+/// Re-derives each output's workspaces from the workspace list. Outputs
+/// without workspaces are dropped (they were unplugged); outputs we haven't
+/// been told about yet get a bare entry until `set_outputs` fills it in.
+fn group_by_output(state: &mut NiriState) {
+    for output in &mut state.outputs {
+        output.workspace_ids.clear();
+        output.active_workspace_id = None;
+    }
+
+    let mut workspaces: Vec<&Workspace> = state.workspaces.iter().collect();
+    workspaces.sort_by_key(|ws| ws.idx);
+    for ws in workspaces {
+        let Some(name) = &ws.output else { continue };
+        let output = match state.outputs.iter().position(|o| &o.name == name) {
+            Some(i) => &mut state.outputs[i],
+            None => {
+                state.outputs.push(OutputState {
+                    name: name.clone(),
+                    make: None,
+                    model: None,
+                    logical: None,
+                    workspace_ids: Vec::new(),
+                    active_workspace_id: None,
+                });
+                state.outputs.last_mut().unwrap()
+            }
+        };
+        output.workspace_ids.push(ws.id);
+        if ws.is_active {
+            output.active_workspace_id = Some(ws.id);
+        }
+    }
+
+    state.outputs.retain(|o| !o.workspace_ids.is_empty());
+    state.outputs.sort_by(|a, b| {
+        let pos = |o: &OutputState| o.logical.map(|l| (l.x, l.y));
+        (pos(a).is_none(), pos(a), &a.name).cmp(&(pos(b).is_none(), pos(b), &b.name))
+    });
+}
+
+// ── Windows ───────────────────────────────────────────────────────────────────
+
+fn bucket(window: &Window) -> u64 {
+    window.workspace_id.unwrap_or(NO_WORKSPACE)
+}
+
+fn remove_window(state: &mut NiriState, id: u64) {
+    for wins in state.windows_by_workspace.values_mut() {
+        wins.retain(|w| w.id != id);
+    }
+}
+
+fn set_focused_window(state: &mut NiriState, id: Option<u64>) {
+    state.focused_window_id = id;
+    for w in state.windows_by_workspace.values_mut().flatten() {
+        w.is_focused = Some(w.id) == id;
+    }
+    if let Some(id) = id {
+        touch_mru(state, id);
+    }
+}
+
+// ── Layout order ──────────────────────────────────────────────────────────────
+
+/// Orders every workspace's windows as they sit on screen: tiled windows by
+/// column, then by tile within the column; floating windows (which have no
+/// scrolling-layout position) after them. Also rebuilds the column grouping.
+fn sort_by_layout(state: &mut NiriState) {
+    state.columns_by_workspace.clear();
+    for (ws_id, wins) in &mut state.windows_by_workspace {
+        wins.sort_by_key(|w| {
+            let pos = w.layout.pos_in_scrolling_layout;
+            (pos.is_none(), pos, w.id)
+        });
+
+        let columns = state.columns_by_workspace.entry(*ws_id).or_default();
+        let mut current = None;
+        for win in wins.iter() {
+            let Some((column, _)) = win.layout.pos_in_scrolling_layout else { break };
+            if current != Some(column) {
+                columns.push(Vec::new());
+                current = Some(column);
+            }
+            columns.last_mut().unwrap().push(win.id);
+        }
+    }
+}
+
+// ── Recently used ─────────────────────────────────────────────────────────────
+
+fn touch_mru(state: &mut NiriState, id: u64) {
+    // Showing the overlay focuses it; that mustn't bury the window under it.
+    if state.is_own_window(id) {
+        return;
+    }
+    state.mru.retain(|w| *w != id);
+    state.mru.insert(0, id);
+}
+
+/// Drops closed windows from the MRU list, appends windows it doesn't know
+/// yet (most recent niri focus timestamp first) and re-splits it by workspace.
+fn sync_mru(state: &mut NiriState) {
+    let windows: HashMap<u64, &Window> = state.windows_by_workspace.values().flatten()
+    .filter(|w| !is_own_window(w))
+    .map(|w| (w.id, w))
+    .collect();
+
+    state.mru.retain(|id| windows.contains_key(id));
+    let mut unseen: Vec<&Window> = windows.values()
+    .filter(|w| !state.mru.contains(&w.id))
+    .copied()
+    .collect();
+    unseen.sort_by_key(|w| (Reverse(w.focus_timestamp.map(|t| (t.secs, t.nanos))), w.id));
+    state.mru.extend(unseen.iter().map(|w| w.id));
+
+    state.mru_by_workspace.clear();
+    for id in &state.mru {
+        state.mru_by_workspace.entry(bucket(windows[id])).or_default().push(*id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! Golden fixtures live in `tests/fixtures/reducer/*.json`:
+    //!
+    //! ```json
+    //! { "events": [ ... ], "expected": { ... } }
+    //! ```
+    //!
+    //! Events are niri's wire format, except that windows, workspaces and
+    //! window layouts only need the fields the fixture cares about; the rest
+    //! are filled in from the templates below, and `"pid": "self"` stands for
+    //! the test process, i.e. the overlay's own window. `expected` is matched
+    //! against the serialized state as a subset: objects need only list the
+    //! keys they check, arrays must match in length and element by element.
+
+    use super::*;
+    use serde_json::{json, Value};
+    use std::path::{Path, PathBuf};
+
+    fn window_template() -> Value {
+        json!({
+            "id": 0, "title": null, "app_id": null, "pid": null, "workspace_id": null,
+            "is_focused": false, "is_floating": false, "is_urgent": false,
+            "layout": layout_template(), "focus_timestamp": null
+        })
+    }
+
+    fn layout_template() -> Value {
+        json!({
+            "pos_in_scrolling_layout": null, "tile_size": [0.0, 0.0], "window_size": [0, 0],
+            "tile_pos_in_workspace_view": null, "window_offset_in_tile": [0.0, 0.0]
+        })
+    }
+
+    fn workspace_template() -> Value {
+        json!({
+            "id": 0, "idx": 1, "name": null, "output": "MOCK-1", "is_urgent": false,
+            "is_active": false, "is_focused": false, "active_window_id": null
+        })
+    }
+
+    /// Merges `partial` over `template`, recursing into nested objects.
+    fn fill(template: Value, partial: &Value) -> Value {
+        match (template, partial) {
+            (Value::Object(mut full), Value::Object(partial)) => {
+                for (key, value) in partial {
+                    let merged = match full.remove(key) {
+                        Some(inner) => fill(inner, value),
+                        None => value.clone(),
+                    };
+                    full.insert(key.clone(), merged);
+                }
+                Value::Object(full)
+            }
+            (_, partial) => partial.clone(),
+        }
+    }
+
+    /// Expands the abbreviated windows/workspaces/layouts in a fixture event.
+    fn complete(mut event: Value) -> Value {
+        let Some(body) = event.as_object_mut().and_then(|e| e.values_mut().next()) else { return event };
+        let Some(body) = body.as_object_mut() else { return event };
+        for (key, value) in body.iter_mut() {
+            let fill_one: fn(&Value) -> Value = match key.as_str() {
+                "window" | "windows" => |w| {
+                    let mut w = fill(window_template(), w);
+                    if w["pid"] == "self" {
+                        w["pid"] = json!(std::process::id());
+                    }
+                    w
+                },
+                "workspaces" => |ws| fill(workspace_template(), ws),
+                // WindowLayoutsChanged: `[id, layout]` pairs.
+                "changes" => |change| json!([change[0], fill(layout_template(), &change[1])]),
+                _ => continue,
+            };
+            *value = match &*value {
+                Value::Array(items) => items.iter().map(fill_one).collect(),
+                single => fill_one(single),
+            };
+        }
+        event
+    }
+
+    fn assert_subset(expected: &Value, actual: &Value, at: &str) {
+        match (expected, actual) {
+            (Value::Object(exp), Value::Object(act)) => {
+                for (key, exp) in exp {
+                    let act = act.get(key).unwrap_or(&Value::Null);
+                    assert_subset(exp, act, &format!("{at}.{key}"));
+                }
+            }
+            (Value::Array(exp), Value::Array(act)) => {
+                assert_eq!(exp.len(), act.len(), "{at}: expected {expected}, got {actual}");
+                for (i, (exp, act)) in exp.iter().zip(act).enumerate() {
+                    assert_subset(exp, act, &format!("{at}[{i}]"));
+                }
+            }
+            _ => assert_eq!(expected, actual, "{at}"),
+        }
+    }
+
+    fn fixtures() -> Vec<PathBuf> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/reducer");
+        let mut paths: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("{}: {e}", dir.display()))
+        .map(|entry| entry.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn outputs_keep_their_details_across_workspace_changes() {
+        use crate::ipc::mock::{output, workspace};
+
+        let mut state = NiriState::default();
+        let mut right = workspace(2, 1);
+        right.output = Some("RIGHT".into());
+        reduce(&mut state, &NiriEvent::WorkspacesChanged { workspaces: vec![workspace(1, 1), right.clone()] });
+        assert!(state.has_undescribed_outputs());
+
+        let outputs = [("RIGHT".into(), output("RIGHT", 1920)), ("MOCK-1".into(), output("MOCK-1", 0))];
+        set_outputs(&mut state, &outputs.into());
+        assert!(!state.has_undescribed_outputs());
+        let names: Vec<_> = state.outputs.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, ["MOCK-1", "RIGHT"], "ordered left to right");
+
+        // Unplugging RIGHT moves its workspace over; RIGHT drops out.
+        right.output = Some("MOCK-1".into());
+        right.idx = 2;
+        reduce(&mut state, &NiriEvent::WorkspacesChanged { workspaces: vec![workspace(1, 1), right] });
+        assert_eq!(state.outputs.len(), 1);
+        assert_eq!(state.outputs[0].model.as_deref(), Some("Display"));
+        assert_eq!(state.outputs[0].workspace_ids, [1, 2]);
+    }
+
+    #[test]
+    fn previous_window_looks_past_the_overlay() {
+        use crate::ipc::mock::window;
+
+        let mut state = NiriState::default();
+        let mut overlay = window(20, 1);
+        overlay.pid = Some(std::process::id() as i32);
+        reduce(&mut state, &NiriEvent::WindowsChanged { windows: vec![window(10, 1), window(11, 1), overlay] });
+        reduce(&mut state, &NiriEvent::WindowFocusChanged { id: Some(11) });
+        reduce(&mut state, &NiriEvent::WindowFocusChanged { id: Some(10) });
+        assert_eq!(state.previous_window(), Some(11));
+
+        reduce(&mut state, &NiriEvent::WindowFocusChanged { id: Some(20) });
+        assert_eq!(state.mru, [10, 11]);
+        assert_eq!(state.previous_window(), Some(11));
+    }
+
+    #[test]
+    fn reducer_matches_golden_fixtures() {
+        let paths = fixtures();
+        assert!(!paths.is_empty(), "no reducer fixtures found");
+
+        for path in paths {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let fixture: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap())
+            .unwrap_or_else(|e| panic!("{name}: {e}"));
+
+            let mut state = NiriState::default();
+            for (i, event) in fixture["events"].as_array().expect("events").iter().enumerate() {
+                let event: NiriEvent = serde_json::from_value(complete(event.clone()))
+                .unwrap_or_else(|e| panic!("{name}: event {i}: {e}"));
+                reduce(&mut state, &event);
+            }
+
+            assert_subset(&fixture["expected"], &serde_json::to_value(&state).unwrap(), &name);
+        }
+    }
+}
@@ -1,22 +1,52 @@
 /// Re-export niri's official IPC types.
 pub use niri_ipc::{Event as NiriEvent, Request as NiriRequest, Response as NiriResponse};
-pub use niri_ipc::{Window, Workspace};
+pub use niri_ipc::{KeyboardLayouts, LogicalOutput, Output, Window, Workspace};
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::error::CommandError;
 
 // ── Socket path ───────────────────────────────────────────────────────────────
 
-pub fn socket_path() -> anyhow::Result<std::path::PathBuf> {
-    if let Ok(p) = std::env::var("NIRI_SOCKET") {
-        return Ok(p.into());
+/// niri's IPC socket. niri names it `niri.<display>.<pid>.sock`, so the
+/// `NIRI_SOCKET` we inherited goes stale when niri restarts; in that case the
+/// newest niri socket in the same directory is the one to use.
+pub fn socket_path() -> anyhow::Result<PathBuf> {
+    let from_env = std::env::var_os("NIRI_SOCKET").map(PathBuf::from);
+    if let Some(path) = from_env.as_ref().filter(|p| p.exists()) {
+        return Ok(path.clone());
+    }
+    let dir = from_env.as_deref().and_then(Path::parent).map_or_else(runtime_dir, Path::to_path_buf);
+    if let Some(path) = newest_niri_socket(&dir) {
+        return Ok(path);
     }
-    let runtime = std::env::var("XDG_RUNTIME_DIR")
-    .unwrap_or_else(|_| format!("/run/user/{}", read_uid()));
-    Ok(std::path::PathBuf::from(format!("{runtime}/niri/socket")))
+    Ok(from_env.unwrap_or_else(|| runtime_dir().join("niri/socket")))
+}
+
+/// Whether a file name looks like one of niri's sockets.
+pub fn is_niri_socket_name(name: &OsStr) -> bool {
+    name.to_str().is_some_and(|n| n.starts_with("niri.") && n.ends_with(".sock"))
 }
 
-fn read_uid() -> u32 {
+fn newest_niri_socket(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir).ok()?
+    .flatten()
+    .filter(|entry| is_niri_socket_name(&entry.file_name()))
+    .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+    .max_by_key(|(modified, _)| *modified)
+    .map(|(_, path)| path)
+}
+
+fn runtime_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+    .map_or_else(|| PathBuf::from(format!("/run/user/{}", read_uid())), PathBuf::from)
+}
+
+pub(crate) fn read_uid() -> u32 {
     std::fs::read_to_string("/proc/self/status")
     .ok()
     .and_then(|s| {
@@ -28,18 +58,210 @@ fn read_uid() -> u32 {
     .unwrap_or(1000)
 }
 
+/// Whether `window` belongs to this process.
+pub fn is_own_window(window: &Window) -> bool {
+    window.pid.is_some_and(|pid| pid as u32 == std::process::id())
+}
+
 // ── Aggregated state ──────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct NiriState {
     pub workspaces: Vec<Workspace>,
+    /// Each workspace's windows in on-screen order: tiled windows column by
+    /// column, then floating ones.
     pub windows_by_workspace: HashMap<u64, Vec<Window>>,
+    /// Window ids of each workspace's scrolling-layout columns, left to right,
+    /// tiles top to bottom. Floating windows aren't in any column.
+    pub columns_by_workspace: HashMap<u64, Vec<Vec<u64>>>,
     pub focused_window_id: Option<u64>,
     pub focused_workspace_id: Option<u64>,
+    /// Monitors that have workspaces, left to right.
+    pub outputs: Vec<OutputState>,
+    /// Window ids, most recently focused first. Windows never focused since
+    /// the daemon started follow in order of niri's focus timestamps.
+    pub mru: Vec<u64>,
+    /// [`mru`](Self::mru) split by workspace id.
+    pub mru_by_workspace: HashMap<u64, Vec<u64>>,
+    /// `None` until niri reports its layouts (it does so on connect).
+    pub keyboard_layouts: Option<KeyboardLayouts>,
+    pub overview_open: bool,
+    /// Whether niri's last config (re)load failed.
+    pub config_failed: bool,
+    /// The event stream's health. While `connected` is false everything
+    /// above is the last state seen and may be stale.
+    pub connection: ConnectionStatus,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionStatus {
+    pub connected: bool,
+    /// When the stream was lost (ms since the Unix epoch); `None` while
+    /// connected and before the first attempt.
+    pub disconnected_since_ms: Option<u64>,
+    /// Why the stream was last lost or couldn't be opened.
+    pub last_error: Option<String>,
+    /// How often the stream has been re-established after the first connect.
+    pub reconnects: u32,
+    /// What niri answered `Version` with on the last connect, e.g.
+    /// `25.11 (b35bcae)`.
+    pub niri_version: Option<String>,
+    pub compatibility: Compatibility,
+}
+
+/// The niri-ipc release this build speaks. Cargo.toml pins niri-ipc to
+/// exactly this version; bump the two together.
+pub const NIRI_IPC_VERSION: &str = "25.11.0";
+
+/// How the running niri compares to [`NIRI_IPC_VERSION`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compatibility {
+    /// Not checked yet, or niri's version couldn't be read.
+    #[default]
+    Unknown,
+    /// The same niri release our niri-ipc was cut from.
+    Matched,
+    /// Another release. Events it added are skipped, and anything it changed
+    /// may be misread.
+    Mismatched,
+}
+
+impl Compatibility {
+    pub fn of(niri_version: &str) -> Self {
+        match (release(niri_version), release(NIRI_IPC_VERSION)) {
+            (Some(niri), Some(ours)) if niri == ours => Self::Matched,
+            (Some(_), Some(_)) => Self::Mismatched,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// niri releases are `<year>.<month>`; the patch level and commit don't
+/// change the protocol.
+fn release(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version
+    .trim_start_matches(|c: char| !c.is_ascii_digit())
+    .split(|c: char| !c.is_ascii_digit());
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+/// Whether niri-ipc knows the event called `name`. A newer niri may send
+/// events it doesn't; those are skipped rather than reported as garbage.
+pub fn is_known_event(name: &str) -> bool {
+    static VARIANTS: OnceLock<&'static [&'static str]> = OnceLock::new();
+    VARIANTS.get_or_init(event_variants).contains(&name)
+}
+
+/// The variant names [`NiriEvent`]'s `Deserialize` impl accepts, read off
+/// the list it hands to the deserializer.
+fn event_variants() -> &'static [&'static str] {
+    use serde::de::{self, Visitor};
+
+    struct Variants(&'static [&'static str]);
+
+    impl<'de> de::Deserializer<'de> for &mut Variants {
+        type Error = de::value::Error;
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            variants: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            self.0 = variants;
+            Err(de::Error::custom("only reading variant names"))
+        }
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not an enum"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+            byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct map
+            struct identifier ignored_any
+        }
+    }
+
+    let mut variants = Variants(&[]);
+    let _ = NiriEvent::deserialize(&mut variants);
+    variants.0
+}
+
+/// A monitor and the workspaces niri keeps on it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputState {
+    /// Connector name, e.g. `eDP-1`; what workspaces refer to.
+    pub name: String,
+    /// `None` until the daemon has asked niri to describe this output.
+    pub make: Option<String>,
+    pub model: Option<String>,
+    /// Position, size and scale in niri's logical coordinate space; `None`
+    /// when the output is disabled or not described yet.
+    pub logical: Option<LogicalOutput>,
+    /// Ids of the workspaces on this output, by index.
+    pub workspace_ids: Vec<u64>,
+    pub active_workspace_id: Option<u64>,
 }
 
 impl NiriState {
     pub fn total_windows(&self) -> usize {
         self.windows_by_workspace.values().map(Vec::len).sum()
     }
+
+    /// The window focused before the current one, for "focus previous window".
+    /// While the overlay has focus, the current one is the window under it.
+    pub fn previous_window(&self) -> Option<u64> {
+        let current = match self.focused_window_id {
+            Some(id) if self.is_own_window(id) => self.mru.first().copied(),
+            focused => focused,
+        };
+        self.mru.iter().copied().find(|id| Some(*id) != current)
+    }
+
+    /// Whether `id` is the overlay's own window, which niri lists like any
+    /// other toplevel but which is never one to switch back to.
+    pub fn is_own_window(&self, id: u64) -> bool {
+        self.windows_by_workspace.values().flatten().any(|w| w.id == id && is_own_window(w))
+    }
+
+    /// Whether some workspace sits on an output niri hasn't described to us
+    /// yet. niri sends no output events, so this is the cue to ask.
+    pub fn has_undescribed_outputs(&self) -> bool {
+        self.outputs.iter().any(|o| o.make.is_none())
+    }
+
+    /// Workspaces by output, left to right, then by index. Workspaces on an
+    /// output we have no details for come last.
+    pub fn workspaces_in_order(&self) -> Vec<&Workspace> {
+        let output_rank = |ws: &Workspace| {
+            self.outputs.iter().position(|o| Some(&o.name) == ws.output.as_ref()).unwrap_or(usize::MAX)
+        };
+        let mut workspaces: Vec<_> = self.workspaces.iter().collect();
+        workspaces.sort_by_key(|ws| (output_rank(ws), ws.output.clone(), ws.idx));
+        workspaces
+    }
+
+    pub fn has_window(&self, id: u64) -> bool {
+        self.windows_by_workspace.values().flatten().any(|w| w.id == id)
+    }
+
+    /// Canonical connector name of the output called `name`, matched
+    /// case-insensitively as niri does. niri ignores moves to unknown
+    /// outputs without an error, so callers check here first.
+    pub fn resolve_output(&self, name: &str) -> Result<String, CommandError> {
+        self.outputs.iter()
+        .find(|o| o.name.eq_ignore_ascii_case(name))
+        .map(|o| o.name.clone())
+        .ok_or_else(|| CommandError::UnknownOutput { name: name.into() })
+    }
+
+    pub fn window_mut(&mut self, id: u64) -> Option<&mut Window> {
+        self.windows_by_workspace.values_mut().flatten().find(|w| w.id == id)
+    }
+
+    pub fn workspace_mut(&mut self, id: u64) -> Option<&mut Workspace> {
+        self.workspaces.iter_mut().find(|ws| ws.id == id)
+    }
 }
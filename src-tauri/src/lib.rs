@@ -7,57 +7,78 @@ use tauri::{
 use tracing::info;
 use tracing_subscriber::{fmt, EnvFilter};
 
+pub mod cli;
 mod commands;
+pub mod control;
+mod cycle;
+pub mod error;
+mod icons;
 mod ipc;
+mod watch;
 
 use commands::{
-    close_window, debug_icon_search, focus_window, focus_workspace,
-    get_app_icon, get_state, move_window_to_workspace,
-    AppState,
+    close_window, cycle_cancel, cycle_commit, debug_icon_search, focus_previous_window,
+    focus_window, focus_workspace, get_app_icon, get_state, move_window_to_monitor,
+    move_window_to_workspace, move_workspace_to_monitor, AppState,
 };
 use ipc::NiriClient;
 
-// ── Toggle socket path ────────────────────────────────────────────────────────
-// niri-switch --toggle connects here and sends a single byte; the daemon
-// receives it and shows/hides the overlay.
-
-fn toggle_socket_path() -> std::path::PathBuf {
-    let uid = read_uid();
-    let runtime = std::env::var("XDG_RUNTIME_DIR")
-    .unwrap_or_else(|_| format!("/run/user/{uid}"));
-    std::path::PathBuf::from(format!("{runtime}/niri-switch.sock"))
-}
-
-fn read_uid() -> u32 {
-    std::fs::read_to_string("/proc/self/status")
-    .ok()
-    .and_then(|s| {
-        s.lines()
-        .find(|l| l.starts_with("Uid:"))
-        .and_then(|l| l.split_whitespace().nth(1))
-        .and_then(|uid| uid.parse().ok())
-    })
-    .unwrap_or(1000)
+/// Daemon startup options, parsed from the command line by `cli`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DaemonOptions {
+    /// Ask an already running daemon to quit and take over its socket.
+    pub replace: bool,
+    /// Write every raw niri event line to this file, with timestamps.
+    pub record: Option<std::path::PathBuf>,
+    /// Feed this recording into the state instead of connecting to niri.
+    pub replay: Option<std::path::PathBuf>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    run_with(DaemonOptions::default());
+}
+
+pub fn run_with(opts: DaemonOptions) {
     fmt()
     .with_env_filter(EnvFilter::from_default_env())
     .init();
 
-    let (client, mut event_rx) = NiriClient::new();
+    let control_listener = match control::instance::claim(opts.replace) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("niri-switch: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let (mut client, mut event_rx) = NiriClient::new();
+    if let Some(path) = &opts.record {
+        if let Err(e) = client.record_to(path) {
+            eprintln!("niri-switch: cannot record to {}: {e}", path.display());
+            std::process::exit(1);
+        }
+    }
     let client = Arc::new(client);
+    let icon_resolver = Arc::new(icons::IconResolver::default());
+    let desktop_entries = icon_resolver.desktop_entries();
     let client_for_stream = Arc::clone(&client);
 
     tauri::Builder::default()
     .manage(AppState { client: Arc::clone(&client) })
+    .manage(cycle::Cycler::default())
+    .manage(icon_resolver)
     .invoke_handler(tauri::generate_handler![
         get_state,
         focus_window,
+        focus_previous_window,
         focus_workspace,
         move_window_to_workspace,
+        move_window_to_monitor,
+        move_workspace_to_monitor,
         close_window,
+        cycle_commit,
+        cycle_cancel,
         get_app_icon,
         debug_icon_search,
     ])
@@ -83,42 +104,57 @@ pub fn run() {
         })
         .build(app)?;
 
-        // ── Niri event stream → frontend ──────────────────────────────────
+        // ── Niri state → frontend ─────────────────────────────────────────
+        // The overlay renders the daemon's reduced state rather than folding
+        // events itself. Events that pile up during a burst (or overrun the
+        // channel) go out as one snapshot.
         let app_handle = app.handle().clone();
+        let client_for_events = Arc::clone(&client);
+        let mut state_rx = client.state_tx.subscribe();
         tauri::async_runtime::spawn(async move {
-            while let Ok(event) = event_rx.recv().await {
-                let event: ipc::NiriEvent = event;
-                let payload = serde_json::to_value(&event).unwrap_or_default();
-                let _ = app_handle.emit("niri://event", payload);
+            use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+            loop {
+                tokio::select! {
+                    received = event_rx.recv() => match received {
+                        Ok(_) | Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => return,
+                    },
+                    changed = state_rx.changed() => if changed.is_err() { return },
+                }
+                while let Ok(_) | Err(TryRecvError::Lagged(_)) = event_rx.try_recv() {}
+                let state = client_for_events.state.read().await.clone();
+                let _ = app_handle.emit("niri://state", state);
             }
         });
 
+        let app_handle = app.handle().clone();
+        let mut connection_rx = client.connection_tx.subscribe();
         tauri::async_runtime::spawn(async move {
-            client_for_stream.run_event_stream().await;
+            while connection_rx.changed().await.is_ok() {
+                let status = connection_rx.borrow_and_update().clone();
+                let _ = app_handle.emit("niri://connection", status);
+            }
         });
 
-        // ── Toggle socket listener ────────────────────────────────────────
-        // Listens for connections on $XDG_RUNTIME_DIR/niri-switch.sock.
-        // Any connection (even zero bytes) triggers a toggle.
-        let sock_path = toggle_socket_path();
-        // Remove stale socket from a previous run
-        let _ = std::fs::remove_file(&sock_path);
+        let replay = opts.replay.clone();
+        tauri::async_runtime::spawn(async move {
+            match replay {
+                Some(path) => client_for_stream.run_replay(path).await,
+                None => client_for_stream.run_event_stream().await,
+            }
+        });
 
+        // Keep the desktop entry index behind app icons current as apps
+        // are installed and removed.
+        tauri::async_runtime::spawn(desktop_entries.watch());
+
+        // ── Control socket listener ───────────────────────────────────────
+        // Serves the line protocol in control::protocol on
+        // $XDG_RUNTIME_DIR/niri-switch.sock (show/hide/toggle, actions, …).
         let app_handle = app.handle().clone();
+        let client_for_control = Arc::clone(&client);
         tauri::async_runtime::spawn(async move {
-            use tokio::net::UnixListener;
-            match UnixListener::bind(&sock_path) {
-                Ok(listener) => {
-                    info!("Toggle socket listening at {}", sock_path.display());
-                    loop {
-                        // Accept and immediately discard — the connection itself is the signal
-                        if listener.accept().await.is_ok() {
-                            toggle_overlay(&app_handle);
-                        }
-                    }
-                }
-                Err(e) => tracing::error!("Failed to bind toggle socket: {e}"),
-            }
+            control::server::serve(control_listener, app_handle, client_for_control).await;
         });
 
         info!("niri-switch daemon started");
@@ -138,7 +174,9 @@ pub fn run() {
                     let _ = win.hide();
                 }
             }
-            RunEvent::ExitRequested { api, .. } => {
+            // Keep running when the last window closes, but honour explicit
+            // exits (tray "Quit", `niri-switch --replace`).
+            RunEvent::ExitRequested { code: None, api, .. } => {
                 api.prevent_exit();
             }
             _ => {}
@@ -146,13 +184,28 @@ pub fn run() {
     });
 }
 
-pub fn toggle_overlay(app: &AppHandle) {
-    if let Some(win) = app.get_webview_window("overlay") {
-        if win.is_visible().unwrap_or(false) {
-            let _ = win.hide();
-        } else {
-            let _ = win.show();
-            let _ = win.set_focus();
-        }
-    }
+/// Shows the overlay and focuses it. Returns whether it is now visible.
+pub fn show_overlay(app: &AppHandle) -> bool {
+    let Some(win) = app.get_webview_window("overlay") else { return false };
+    let _ = win.show();
+    let _ = win.set_focus();
+    win.is_visible().unwrap_or(true)
+}
+
+/// Hides the overlay. Returns whether it is still visible.
+pub fn hide_overlay(app: &AppHandle) -> bool {
+    let Some(win) = app.get_webview_window("overlay") else { return false };
+    let _ = win.hide();
+    win.is_visible().unwrap_or(false)
+}
+
+/// Flips overlay visibility. Returns whether it is now visible.
+pub fn toggle_overlay(app: &AppHandle) -> bool {
+    if overlay_visible(app) { hide_overlay(app) } else { show_overlay(app) }
+}
+
+pub fn overlay_visible(app: &AppHandle) -> bool {
+    app.get_webview_window("overlay")
+    .and_then(|win| win.is_visible().ok())
+    .unwrap_or(false)
 }
@@ -0,0 +1,74 @@
+//! Async inotify watches on directories.
+//!
+//! Used to notice niri's socket appearing the moment niri (re)starts, rather
+//! than finding out on the next poll, and apps being installed or removed.
+
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+
+use inotify::{Inotify, WatchMask};
+use tokio::io::unix::AsyncFd;
+
+pub struct DirWatch {
+    fd: AsyncFd<Inotify>,
+    buffer: Vec<u8>,
+}
+
+impl DirWatch {
+    /// Watches `dirs` for `mask`. Directories that don't exist are skipped,
+    /// but at least one must be watchable.
+    pub fn new<'a>(dirs: impl IntoIterator<Item = &'a Path>, mask: WatchMask) -> io::Result<Self> {
+        let inotify = Inotify::init()?;
+        let mut last_err = None;
+        let mut watched = 0;
+        for dir in dirs {
+            match inotify.watches().add(dir, mask) {
+                Ok(_) => watched += 1,
+                Err(e) => last_err = Some(e),
+            }
+        }
+        if watched == 0 {
+            return Err(last_err.unwrap_or_else(|| io::Error::other("no directories to watch")));
+        }
+        Ok(Self { fd: AsyncFd::new(inotify)?, buffer: vec![0; 4096] })
+    }
+
+    /// Waits for the next batch of events and returns the names of the
+    /// entries they were about (empty for events on a directory itself).
+    pub async fn changed(&mut self) -> io::Result<Vec<OsString>> {
+        loop {
+            let mut guard = self.fd.readable_mut().await?;
+            let buffer = &mut self.buffer;
+            let read = guard.try_io(|fd| {
+                let events = fd.get_mut().read_events(buffer)?;
+                Ok(events.map(|e| e.name.map(OsString::from).unwrap_or_default()).collect())
+            });
+            match read {
+                Ok(names) => return names,
+                // Spurious wakeup: readiness is cleared, wait again.
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn reports_entries_created_in_a_watched_dir() {
+        let dir = std::env::temp_dir().join(format!("niri-switch-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let missing = dir.join("missing");
+        let mut watch = DirWatch::new([missing.as_path(), dir.as_path()], WatchMask::CREATE).unwrap();
+
+        let _listener = tokio::net::UnixListener::bind(dir.join("niri.test.sock")).unwrap();
+        let names = tokio::time::timeout(Duration::from_secs(5), watch.changed()).await.unwrap().unwrap();
+
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(names, [OsString::from("niri.test.sock")]);
+    }
+}
@@ -7,6 +7,11 @@ const { getCurrentWebviewWindow } = window.__TAURI__.webviewWindow;
 let _navIndex = 0;
 let _navItems = [];
 
+// ── Error reporting ───────────────────────────────────────────────────────────
+// Commands reject with a typed error, `{ kind, ... }` (src-tauri/src/error.rs).
+let _store = null;
+let _bannerTimer = null;
+
 // ── Icon cache ────────────────────────────────────────────────────────────────
 // Map<appId, imgSrc | null>  — null means "not found, use fallback"
 const _iconCache = new Map();
@@ -15,6 +20,7 @@ const _iconLoading = new Set();
 // ── Entry point ───────────────────────────────────────────────────────────────
 
 export function renderApp(root, store) {
+  _store = store;
   root.innerHTML = buildSkeleton();
   bindStaticEvents(root, store);
   renderState(root, store.getState());
@@ -29,12 +35,14 @@ function buildSkeleton() {
   <div class="logo"><div class="logo-pip"></div>niri-switch</div>
   </div>
   <div class="header-meta">
-  <div class="meta-item"><div class="meta-dot"></div><span>niri</span></div>
+  <div class="meta-item"><div class="meta-dot"></div><span id="meta-niri">niri</span></div>
   <span id="meta-ws">— / —</span>
   <span id="meta-wins">— windows</span>
   </div>
   </div>
 
+  <div class="error-banner" id="error-banner" style="display:none"></div>
+
   <div class="search-wrap">
   <div class="search-box">
   <span class="search-icon">⌕</span>
@@ -70,6 +78,7 @@ function buildSkeleton() {
   <div class="kb"><span class="kbd">←→</span> navigate</div>
   <div class="kb"><span class="kbd">↑↓</span> workspace</div>
   <div class="kb"><span class="kbd">Enter</span> focus</div>
+  <div class="kb"><span class="kbd">Tab</span> previous</div>
   <div class="kb"><span class="kbd">M</span> move</div>
   <div class="kb"><span class="kbd">Del</span> close</div>
   <div class="kb"><span class="kbd">1–9</span> jump</div>
@@ -89,6 +98,20 @@ function renderState(root, state) {
   .reduce((a, ws) => a + ws.length, 0);
   const focusedIdx = state.workspaces.findIndex(w => w.is_focused) + 1;
 
+  // While the daemon reconnects to niri the overlay shows the last known state.
+  const live = state.connection.connected;
+  // A niri release other than the one niri-ipc was cut from still works,
+  // minus whatever it changed; say so rather than fail quietly.
+  const mismatch = live && state.connection.compatibility === "mismatched";
+  const niri = root.querySelector("#meta-niri");
+  niri.textContent = !live ? "niri · reconnecting"
+  : mismatch ? `niri ${state.connection.niri_version} · untested` : "niri";
+  niri.title = !live ? state.connection.last_error || ""
+  : mismatch ? "This build targets a different niri release; events it doesn't know are ignored" : "";
+  root.querySelector(".meta-dot").classList.toggle("offline", !live);
+  root.querySelector(".meta-dot").classList.toggle("mismatch", mismatch);
+  root.querySelector(".body").classList.toggle("stale", !live);
+
   root.querySelector("#meta-ws").textContent =
   `ws ${focusedIdx || "-"} / ${state.workspaces.length}`;
   root.querySelector("#meta-wins").textContent =
@@ -108,16 +131,16 @@ function renderWorkspaces(root, state, query) {
   || state.focused_workspace_id
   || state.workspaces[0]?.id;
 
-  list.innerHTML = state.workspaces
-  .filter(ws => !query ||
-  (ws.name || `ws ${ws.idx}`).toLowerCase().includes(query.toLowerCase()))
-  .map(ws => {
+  const matches = ws => !query ||
+  (ws.name || `ws ${ws.idx}`).toLowerCase().includes(query.toLowerCase());
+
+  const item = ws => {
     const wins = state.windows_by_workspace[ws.id] || [];
     const name = ws.name || `ws ${ws.idx}`;
     const sel = ws.id === selectedId;
     const urgentCount = wins.filter(w => w.is_urgent).length;
     return `
-    <div class="ws-item ${sel ? "selected" : ""} ${ws.is_focused ? "focused" : ""}"
+    <div class="ws-item ${sel ? "selected" : ""} ${ws.is_focused ? "focused" : ""} ${ws.is_active ? "active" : ""}"
     data-ws-id="${ws.id}" data-ws-idx="${ws.idx}">
     <div class="ws-left">
     <span class="ws-index">${ws.idx}</span>
@@ -128,6 +151,18 @@ function renderWorkspaces(root, state, query) {
     <span class="ws-badge">${wins.length}</span>
     </div>
     </div>`;
+  };
+
+  // One group per monitor; a single-monitor setup gets no header.
+  list.innerHTML = workspaceGroups(state).map(({ output, workspaces }) => {
+    const items = workspaces.filter(matches).map(item).join("");
+    if (!output || state.outputs.length < 2 || !items) return items;
+    const model = [output.make, output.model].filter(Boolean).join(" ");
+    return `
+    <div class="ws-output" title="${escHtml(model)}">
+    <span class="ws-output-name">${escHtml(output.name)}</span>
+    ${output.logical ? `<span class="ws-output-meta">${output.logical.width}×${output.logical.height} @${output.logical.scale}x</span>` : ""}
+    </div>${items}`;
   }).join("");
 
   list.querySelectorAll(".ws-item").forEach(el => {
@@ -138,14 +173,38 @@ function renderWorkspaces(root, state, query) {
       _navIndex = 0;
       rebuildNavItems(state);
     });
+    el.addEventListener("contextmenu", e => {
+      e.preventDefault();
+      const ws = state.workspaces.find(w => w.id === Number(el.dataset.wsId));
+      if (ws) showWorkspaceMovePopup(el, ws, state);
+    });
     el.addEventListener("dblclick", () => {
-      invoke("focus_workspace", { index: Number(el.dataset.wsIdx) })
+      invoke("focus_workspace", { workspace: { Id: Number(el.dataset.wsId) } })
       .then(() => getCurrentWebviewWindow().hide())
-      .catch(console.error);
+      .catch(reportError);
     });
   });
 }
 
+// Workspaces grouped by output in on-screen order, each group by index.
+// Workspaces niri hasn't put on an output come last.
+function workspaceGroups(state) {
+  const byId = new Map(state.workspaces.map(ws => [ws.id, ws]));
+  const groups = state.outputs.map(output => ({
+    output,
+    workspaces: output.workspace_ids.map(id => byId.get(id)).filter(Boolean),
+  }));
+  const placed = new Set(state.outputs.flatMap(o => o.workspace_ids));
+  const rest = state.workspaces.filter(ws => !placed.has(ws.id));
+  if (rest.length) groups.push({ output: null, workspaces: rest });
+  return groups;
+}
+
+// The sidebar's top-to-bottom order, for ↑/↓ navigation.
+function sidebarWorkspaces(state) {
+  return workspaceGroups(state).flatMap(g => g.workspaces);
+}
+
 // ── Windows ───────────────────────────────────────────────────────────────────
 
 function renderWindows(root, state, query) {
@@ -186,6 +245,7 @@ function renderWindows(root, state, query) {
     const isNavFocused = _navItems[_navIndex]?.id === win.id;
     const isFloating  = win.is_floating === true;
     const isUrgent    = win.is_urgent === true;
+    const pos         = win.layout?.pos_in_scrolling_layout;
     const cachedIcon  = _iconCache.get(win.app_id);
 
     // Kick off icon fetch if not cached yet
@@ -208,6 +268,7 @@ function renderWindows(root, state, query) {
     ${iconContent}
     </div>
     ${isFloating ? `<span class="float-badge" title="Floating">⬡</span>` : ""}
+    ${pos ? `<span class="col-badge" title="Column ${pos[0]}, tile ${pos[1]}">${pos[0]}·${pos[1]}</span>` : ""}
     ${isUrgent   ? `<span class="urgent-badge" title="Urgent">!</span>`  : ""}
     ${isFocused  ? `<span class="active-badge">ACTIVE</span>`            : ""}
     </div>
@@ -231,14 +292,14 @@ function renderWindows(root, state, query) {
       if (e.target.closest(".win-btn")) return;
       invoke("focus_window", { id: Number(card.dataset.winId) })
       .then(() => getCurrentWebviewWindow().hide())
-      .catch(console.error);
+      .catch(reportError);
     });
   });
 
   grid.querySelectorAll(".win-btn.close").forEach(btn => {
     btn.addEventListener("click", e => {
       e.stopPropagation();
-      invoke("close_window", { id: Number(btn.dataset.winId) }).catch(console.error);
+      invoke("close_window", { id: Number(btn.dataset.winId) }).catch(reportError);
     });
   });
 
@@ -281,12 +342,12 @@ function renderStrip(root, state) {
   const selectedId = state.selected_workspace_id || state.focused_workspace_id;
 
   strip.innerHTML = `<span class="strip-label">Layout</span>` +
-  state.workspaces.map(ws => {
+  sidebarWorkspaces(state).map(ws => {
     const wins = state.windows_by_workspace[ws.id] || [];
     return `<div class="strip-col
     ${ws.id === selectedId ? "active" : ""}
     ${wins.length ? "has-wins" : ""}"
-    data-ws-idx="${ws.idx}"
+    data-ws-id="${ws.id}"
     title="ws ${ws.idx}: ${ws.name || "—"} (${wins.length} windows)">
     </div>`;
   }).join("") +
@@ -294,9 +355,9 @@ function renderStrip(root, state) {
 
   strip.querySelectorAll(".strip-col").forEach(col => {
     col.addEventListener("click", () => {
-      invoke("focus_workspace", { index: Number(col.dataset.wsIdx) })
+      invoke("focus_workspace", { workspace: { Id: Number(col.dataset.wsId) } })
       .then(() => getCurrentWebviewWindow().hide())
-      .catch(console.error);
+      .catch(reportError);
     });
   });
 }
@@ -327,29 +388,89 @@ function navActivate() {
   if (!item) return;
   invoke("focus_window", { id: item.id })
   .then(() => getCurrentWebviewWindow().hide())
-  .catch(console.error);
+  .catch(reportError);
+}
+
+// ── Cycling ───────────────────────────────────────────────────────────────────
+// The daemon owns the alt-tab cursor (see cycle.rs); the overlay mirrors it and
+// commits when the held modifier is released.
+
+const CYCLE_MODIFIERS = new Set(["Alt", "Meta", "Super", "Control"]);
+let _cycling = false;
+
+export function showCycleSelection(root, store, windowId) {
+  _cycling = windowId != null;
+  if (!_cycling) return;
+  const state = store.getState();
+  const [wsId] = Object.entries(state.windows_by_workspace)
+  .find(([, wins]) => wins.some(w => w.id === windowId)) ?? [];
+  if (wsId === undefined) return;
+  state.selected_workspace_id = Number(wsId);
+  rebuildNavItems(state);
+  _navIndex = Math.max(0, _navItems.findIndex(item => item.id === windowId));
+  renderState(root, state);
 }
 
 // ── Move popup ────────────────────────────────────────────────────────────────
 
 function showMovePopup(anchor, windowId, state) {
-  const popup = document.getElementById("move-popup");
-  const rect = anchor.getBoundingClientRect();
-  popup.innerHTML = `<div class="move-popup-title">Move to workspace</div>` +
-  state.workspaces.map(ws => `
-  <div class="move-popup-item" data-ws-idx="${ws.idx}">
+  const wsId = Object.keys(state.windows_by_workspace)
+  .find(id => state.windows_by_workspace[id].some(w => w.id === windowId));
+  const current = state.workspaces.find(ws => ws.id === Number(wsId))?.output;
+
+  const workspaces = state.workspaces.map(ws => `
+  <div class="move-popup-item" data-ws-id="${ws.id}">
   <span class="item-idx">${ws.idx}</span>
   <span>${escHtml(ws.name || `ws ${ws.idx}`)}</span>
+  ${state.outputs.length > 1 && ws.output ? `<span class="item-output">${escHtml(ws.output)}</span>` : ""}
+  </div>`).join("");
+
+  openPopup(anchor, `<div class="move-popup-title">Move to workspace</div>` + workspaces +
+  outputItems(state, current, "Move to monitor"), item => {
+    if (item.dataset.output) {
+      invoke("move_window_to_monitor", { windowId, output: item.dataset.output }).catch(reportError);
+    } else {
+      invoke("move_window_to_workspace", {
+        windowId,
+        workspace: { Id: Number(item.dataset.wsId) },
+      }).catch(reportError);
+    }
+  });
+}
+
+function showWorkspaceMovePopup(anchor, ws, state) {
+  const items = outputItems(state, ws.output, `Move ${ws.name || `ws ${ws.idx}`} to monitor`);
+  if (!items) return;
+  openPopup(anchor, items, item => {
+    invoke("move_workspace_to_monitor", {
+      workspace: { Id: ws.id },
+      output: item.dataset.output,
+    }).catch(reportError);
+  });
+}
+
+// Every monitor but `current`; nothing at all on a single-monitor setup.
+function outputItems(state, current, title) {
+  const others = state.outputs.filter(o => o.name !== current);
+  if (!others.length) return "";
+  return `<div class="move-popup-title">${escHtml(title)}</div>` +
+  others.map(o => `
+  <div class="move-popup-item" data-output="${escHtml(o.name)}">
+  <span class="item-idx">⧉</span>
+  <span>${escHtml(o.name)}</span>
+  ${o.model ? `<span class="item-output">${escHtml(o.model)}</span>` : ""}
   </div>`).join("");
+}
 
+function openPopup(anchor, html, onPick) {
+  const popup = document.getElementById("move-popup");
+  const rect = anchor.getBoundingClientRect();
+  popup.innerHTML = html;
   popup.style.cssText = `display:block; top:${Math.min(rect.bottom + 4, window.innerHeight - 200)}px; left:${Math.max(rect.left - 100, 8)}px;`;
 
   popup.querySelectorAll(".move-popup-item").forEach(item => {
     item.addEventListener("click", () => {
-      invoke("move_window_to_workspace", {
-        windowId,
-        workspaceIndex: Number(item.dataset.wsIdx),
-      }).catch(console.error);
+      onPick(item);
       popup.style.display = "none";
     });
   });
@@ -363,12 +484,39 @@ function showMovePopup(anchor, windowId, state) {
   setTimeout(() => document.addEventListener("click", close, true), 0);
 }
 
+// ── Errors ────────────────────────────────────────────────────────────────────
+
+export function reportError(err) {
+  switch (err?.kind) {
+    case "window_gone":
+      // Our copy of the state was a step behind niri: catch up quietly.
+      invoke("get_state").then(s => _store?.setState(s)).catch(console.error);
+      return;
+    case "niri_unavailable":
+    case "timeout":
+      showBanner("niri is not answering — is the compositor still running?");
+      return;
+    default:
+      console.error(err);
+  }
+}
+
+function showBanner(text) {
+  const banner = document.getElementById("error-banner");
+  if (!banner) return;
+  banner.textContent = text;
+  banner.style.display = "block";
+  clearTimeout(_bannerTimer);
+  _bannerTimer = setTimeout(() => { banner.style.display = "none"; }, 4000);
+}
+
 // ── Static event binding ──────────────────────────────────────────────────────
 
 export function bindStaticEvents(root, store) {
   window.addEventListener("keydown", e => {
     if (e.key === "Escape") {
-      getCurrentWebviewWindow().hide().catch(console.error);
+      if (_cycling) invoke("cycle_cancel").catch(reportError);
+      else getCurrentWebviewWindow().hide().catch(console.error);
       return;
     }
     if (isSearchFocused()) return;
@@ -380,7 +528,7 @@ export function bindStaticEvents(root, store) {
 
     if (e.key === "ArrowDown") {
       e.preventDefault();
-      const ws = state.workspaces;
+      const ws = sidebarWorkspaces(state);
       const idx = ws.findIndex(w => w.id === (state.selected_workspace_id || state.focused_workspace_id));
       if (idx < ws.length - 1) {
         state.selected_workspace_id = ws[idx + 1].id;
@@ -391,7 +539,7 @@ export function bindStaticEvents(root, store) {
     }
     if (e.key === "ArrowUp") {
       e.preventDefault();
-      const ws = state.workspaces;
+      const ws = sidebarWorkspaces(state);
       const idx = ws.findIndex(w => w.id === (state.selected_workspace_id || state.focused_workspace_id));
       if (idx > 0) {
         state.selected_workspace_id = ws[idx - 1].id;
@@ -403,14 +551,30 @@ export function bindStaticEvents(root, store) {
 
     if (e.key === "Enter")  { e.preventDefault(); navActivate(); return; }
 
+    if (e.key === "Tab") {
+      e.preventDefault();
+      invoke("focus_previous_window")
+      .then(() => getCurrentWebviewWindow().hide())
+      .catch(reportError);
+      return;
+    }
+
     if (e.key === "Delete" || e.key === "Backspace") {
       e.preventDefault();
       const item = _navItems[_navIndex];
-      if (item) invoke("close_window", { id: item.id }).catch(console.error);
+      if (item) invoke("close_window", { id: item.id }).catch(reportError);
       return;
     }
 
-    if (e.key === "m" || e.key === "M") {
+    if (e.key === "M" && e.shiftKey) {
+      const id = state.selected_workspace_id || state.focused_workspace_id;
+      const ws = state.workspaces.find(w => w.id === id);
+      const el = root.querySelector(`.ws-item[data-ws-id="${id}"]`);
+      if (ws && el) showWorkspaceMovePopup(el, ws, state);
+      return;
+    }
+
+    if (e.key === "m") {
       const item = _navItems[_navIndex];
       if (item) {
         const btn = root.querySelector(`.win-card[data-win-id="${item.id}"] .win-btn.move`);
@@ -419,10 +583,18 @@ export function bindStaticEvents(root, store) {
       return;
     }
 
+    // Number keys keep niri's own meaning: index on the focused output.
     if (e.key >= "1" && e.key <= "9") {
-      invoke("focus_workspace", { index: Number(e.key) })
+      invoke("focus_workspace", { workspace: { Index: Number(e.key) } })
       .then(() => getCurrentWebviewWindow().hide())
-      .catch(console.error);
+      .catch(reportError);
+    }
+  });
+
+  window.addEventListener("keyup", e => {
+    if (_cycling && CYCLE_MODIFIERS.has(e.key)) {
+      _cycling = false;
+      invoke("cycle_commit").catch(reportError);
     }
   });
 
@@ -4,18 +4,29 @@ const { invoke } = window.__TAURI__.core;
 const { listen } = window.__TAURI__.event;
 const { getCurrentWebviewWindow } = window.__TAURI__.webviewWindow;
 
-import { renderApp } from "./lib/render.js";
+import { renderApp, showCycleSelection } from "./lib/render.js";
 import { store } from "./lib/store.js";
 
 async function init() {
   const state = await invoke("get_state");
   store.setState(state);
 
-  await listen("niri://event", ({ payload }) => {
-    store.applyEvent(payload);
+  // The daemon folds every niri event into its state and sends the result.
+  await listen("niri://state", ({ payload }) => {
+    store.setState(payload);
   });
 
-  renderApp(document.getElementById("app"), store);
+  // Connection changes aren't niri events; the daemon reports them separately.
+  await listen("niri://connection", ({ payload }) => {
+    store.setState({ connection: payload });
+  });
+
+  const root = document.getElementById("app");
+  renderApp(root, store);
+
+  await listen("niri-switch://cycle", ({ payload }) => {
+    showCycleSelection(root, store, payload.window_id);
+  });
 
   const win = getCurrentWebviewWindow();
   await win.onFocusChanged(({ payload: focused }) => {